@@ -0,0 +1,86 @@
+//! Measures the throughput of serializing large script filter outputs.
+//!
+//! Run using `cargo bench --bench serialize`.
+
+use std::fs;
+use std::hint::black_box;
+use std::io;
+use std::time::{Duration, Instant};
+
+use powerpack::{Icon, Item, Key, Modifier, Output};
+
+const N: usize = 10_000;
+
+fn plain(i: usize) -> Item {
+    Item::new(format!("Item {i}"))
+        .subtitle(format!("This is item number {i}"))
+        .uid(i.to_string())
+        .arg(format!("/path/to/{i}"))
+}
+
+fn with_icon(i: usize) -> Item {
+    plain(i).icon(Icon::with_file_icon(format!("/path/to/{i}")))
+}
+
+fn with_mods(i: usize) -> Item {
+    with_icon(i)
+        .modifier(Modifier::new(Key::Command).subtitle("Reveal in Finder"))
+        .modifier(Modifier::new(Key::Option).arg(format!("/other/{i}")))
+        .modifier(Modifier::new_multi([Key::Control, Key::Shift]).valid(false))
+}
+
+/// Run `f` repeatedly for about a second and return the mean duration.
+fn measure(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut iters = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        iters += 1;
+    }
+    start.elapsed() / iters
+}
+
+fn report(name: &str, bytes: usize, mean: Duration) {
+    let items_per_sec = N as f64 / mean.as_secs_f64();
+    let mib_per_sec = bytes as f64 / mean.as_secs_f64() / (1024.0 * 1024.0);
+    println!(
+        "{name:<24} {:>10.2?}  {items_per_sec:>12.0} items/s  {mib_per_sec:>8.1} MiB/s",
+        mean
+    );
+}
+
+fn bench_vec(name: &str, item: fn(usize) -> Item) {
+    let mut output = Output::new();
+    output.items((0..N).map(item));
+    let mut buf = Vec::new();
+    output.write(&mut buf).unwrap();
+    let bytes = buf.len();
+    let mean = measure(|| {
+        buf.clear();
+        output.write(black_box(&mut buf)).unwrap();
+    });
+    report(name, bytes, mean);
+}
+
+fn bench_file(name: &str, buffered: bool) {
+    let mut output = Output::new();
+    output.items((0..N).map(with_mods));
+    let bytes = serde_json::to_vec(&output).unwrap().len();
+    let mean = measure(|| {
+        let file = fs::File::create("/dev/null").unwrap();
+        if buffered {
+            output.write(io::BufWriter::new(file)).unwrap();
+        } else {
+            output.write(file).unwrap();
+        }
+    });
+    report(name, bytes, mean);
+}
+
+fn main() {
+    bench_vec("plain", plain);
+    bench_vec("with icon", with_icon);
+    bench_vec("with icon and mods", with_mods);
+    bench_file("unbuffered /dev/null", false);
+    bench_file("buffered /dev/null", true);
+}
@@ -1,9 +1,12 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::io;
 use std::io::prelude::*;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::process;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 pub struct WorkflowInfo {
     pub bundle_id: String,
@@ -11,7 +14,44 @@ pub struct WorkflowInfo {
     pub bin_name: String,
     pub author: String,
     pub description: String,
+    pub version: String,
+    pub category: String,
     pub keyword: String,
+    pub argument_type: ArgumentType,
+    pub readme: String,
+    pub web_address: String,
+    pub variables: BTreeMap<String, String>,
+    pub variables_dont_export: Vec<String>,
+}
+
+/// Whether the script filter takes an argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArgumentType {
+    /// The script filter only runs once the user has typed a query.
+    Required,
+    /// The script filter runs with or without a query.
+    Optional,
+    /// The script filter never takes a query, e.g. a pure launcher.
+    NoArgument,
+}
+
+impl ArgumentType {
+    fn as_plist_value(self) -> u64 {
+        match self {
+            Self::Required => 0,
+            Self::Optional => 1,
+            Self::NoArgument => 2,
+        }
+    }
+}
+
+/// The modification time to record for files in a packaged workflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Timestamp {
+    /// Use a fixed time (1980-01-01) so that packaging is reproducible.
+    Fixed,
+    /// Use the current time, so that it shows when the workflow was built.
+    Now,
 }
 
 macro_rules! dict {
@@ -28,64 +68,197 @@ macro_rules! dict {
     }
 }
 
+/// The unique ID of an object in a workflow [`Graph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uid(String);
+
+#[derive(Debug)]
+struct Object {
+    uid: Uid,
+    kind: String,
+    config: plist::Value,
+    disabled: bool,
+}
+
+/// A graph of workflow objects and the connections between them.
+///
+/// This produces the `objects`, `connections`, and `uidata` entries of an
+/// `info.plist` file. Objects are laid out automatically from left to right
+/// in the order they are connected.
+#[derive(Debug, Default)]
+pub struct Graph {
+    objects: Vec<Object>,
+    connections: Vec<(Uid, Uid)>,
+}
+
+impl Graph {
+    const X_START: i64 = 50;
+    const Y_START: i64 = 50;
+    const X_STEP: i64 = 175;
+    const Y_STEP: i64 = 120;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an object of the given type, e.g.
+    /// `alfred.workflow.input.scriptfilter`, to the graph.
+    pub fn add_object(&mut self, kind: &str, config: plist::Value) -> Uid {
+        self.push_object(kind, config, false)
+    }
+
+    /// Add an object to the graph like [`Graph::add_object`] but mark it as
+    /// disabled, so that it does nothing until the user enables it in Alfred
+    /// Preferences.
+    #[allow(dead_code)]
+    pub fn add_object_disabled(&mut self, kind: &str, config: plist::Value) -> Uid {
+        self.push_object(kind, config, true)
+    }
+
+    fn push_object(&mut self, kind: &str, config: plist::Value, disabled: bool) -> Uid {
+        let uid = Uid(uuid::Uuid::new_v4().to_string().to_uppercase());
+        self.objects.push(Object {
+            uid: uid.clone(),
+            kind: kind.to_owned(),
+            config,
+            disabled,
+        });
+        uid
+    }
+
+    /// Connect the output of one object to the input of another.
+    pub fn connect(&mut self, from: &Uid, to: &Uid) {
+        self.connections.push((from.clone(), to.clone()));
+    }
+
+    /// Returns the `objects` array.
+    pub fn objects(&self) -> plist::Value {
+        self.objects
+            .iter()
+            .map(|obj| {
+                let mut value = dict! {
+                    "uid" => obj.uid.0,
+                    "type" => obj.kind,
+                    "config" => obj.config
+                };
+                if obj.disabled {
+                    let dict = value.as_dictionary_mut().unwrap();
+                    dict.insert("disabled".to_owned(), true.into());
+                    dict.sort_keys();
+                }
+                value
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// Returns the `connections` dictionary.
+    pub fn connections(&self) -> plist::Value {
+        let mut dict = plist::Dictionary::new();
+        for (from, to) in &self.connections {
+            let dst = dict! { "destinationuid" => to.0 };
+            match dict.get_mut(&from.0).and_then(|v| v.as_array_mut()) {
+                Some(dsts) => dsts.push(dst),
+                None => {
+                    dict.insert(from.0.clone(), vec![dst].into());
+                }
+            }
+        }
+        plist::Value::Dictionary(dict)
+    }
+
+    /// Returns the `uidata` dictionary containing the position of each
+    /// object.
+    ///
+    /// Each object is placed in a column according to the longest chain of
+    /// connections leading to it, and then in rows in the order it was added.
+    /// Objects that are part of a cycle are placed in the last column.
+    pub fn uidata(&self) -> plist::Value {
+        let index = |uid: &Uid| self.objects.iter().position(|obj| &obj.uid == uid);
+
+        // Depths are capped at the number of objects so that cycles don't
+        // increase them indefinitely, objects in a cycle end up in the last
+        // column instead
+        let n = self.objects.len();
+        let mut depths = vec![0; n];
+        for _ in 0..n {
+            for (from, to) in &self.connections {
+                if let (Some(a), Some(b)) = (index(from), index(to)) {
+                    depths[b] = depths[b].max((depths[a] + 1).min(n - 1));
+                }
+            }
+        }
+
+        let mut rows = vec![0; self.objects.len()];
+        let mut dict = plist::Dictionary::new();
+        for (obj, depth) in self.objects.iter().zip(depths) {
+            let row = &mut rows[depth];
+            let xpos = Self::X_START + Self::X_STEP * depth as i64;
+            let ypos = Self::Y_START + Self::Y_STEP * *row;
+            *row += 1;
+            dict.insert(obj.uid.0.clone(), dict! { "xpos" => xpos, "ypos" => ypos });
+        }
+        plist::Value::Dictionary(dict)
+    }
+}
+
 /// Builds an Alfred workflow `info.plist` file.
 ///
 /// This is just a simple script filter to clipboard workflow.
 pub fn build_info_plist(info: &WorkflowInfo) -> plist::Value {
-    let uid_a = uuid::Uuid::new_v4().to_string().to_uppercase();
-    let uid_b = uuid::Uuid::new_v4().to_string().to_uppercase();
+    let mut graph = Graph::new();
+    let script_filter = graph.add_object(
+        "alfred.workflow.input.scriptfilter",
+        dict! {
+            "keyword" => info.keyword,
+            "withspace" => info.argument_type != ArgumentType::NoArgument,
+            // Argument required, optional, or no argument
+            "argumenttype" => info.argument_type.as_plist_value(),
+            // Placeholder title
+            "title" => "Search",
+            // "Please wait" subtext
+            "runningsubtext" => "Loading...",
+            // External script
+            "type" => 8,
+            "scriptfile" => info.bin_name,
+            // Terminate previous script
+            "queuemode" => 2,
+            // Always run immediately for first typed character
+            "queuedelayimmediatelyinitially" => true,
+            // Don't set argv when empty
+            "argumenttreatemptyqueryasnil" => true
+        },
+    );
+    let clipboard = graph.add_object(
+        "alfred.workflow.output.clipboard",
+        dict! {
+            "clipboardtext" => "{query}"
+        },
+    );
+    graph.connect(&script_filter, &clipboard);
+
     dict! {
         "name" => info.name,
         "description" => info.description,
+        "version" => info.version,
+        "category" => info.category,
         "bundleid" => info.bundle_id,
         "createdby" => info.author,
-        "connections" => dict! {
-            uid_a => vec![
-                dict! { "destinationuid" => uid_b }
-            ]
-        },
-        "uidata" => dict! {
-            uid_a => dict! {
-                "xpos" => 50,
-                "ypos" => 50
-            },
-            uid_b => dict! {
-                "xpos" => 225,
-                "ypos" => 50
-            }
-        },
-        "objects" => vec![
-            dict! {
-                "uid" => uid_b,
-                "type" => "alfred.workflow.output.clipboard",
-                "config" => dict! {
-                    "clipboardtext" => "{query}"
-                }
-            },
-            dict! {
-                "uid" => uid_a,
-                "type" => "alfred.workflow.input.scriptfilter",
-                "config" => dict! {
-                    "keyword" => info.keyword,
-                    "withspace" => true,
-                    // Argument optional
-                    "argumenttype" => 1,
-                    // Placeholder title
-                    "title" => "Search",
-                    // "Please wait" subtext
-                    "runningsubtext" => "Loading...",
-                    // External script
-                    "type" => 8,
-                    "scriptfile" => info.bin_name,
-                    // Terminate previous script
-                    "queuemode" => 2,
-                    // Always run immediately for first typed character
-                    "queuedelayimmediatelyinitially" => true,
-                    // Don't set argv when empty
-                    "argumenttreatemptyqueryasnil" => true
-                }
-            }
-        ]
+        "readme" => info.readme,
+        "webaddress" => info.web_address,
+        "variables" => info
+            .variables
+            .iter()
+            .map(|(k, v)| (k.clone(), plist::Value::from(v.clone())))
+            .collect::<plist::Dictionary>(),
+        "variablesdontexport" => info
+            .variables_dont_export
+            .iter()
+            .map(|k| k.clone().into())
+            .collect::<Vec<plist::Value>>(),
+        "connections" => graph.connections(),
+        "uidata" => graph.uidata(),
+        "objects" => graph.objects()
     }
 }
 
@@ -118,18 +291,227 @@ pub fn workflows_directory() -> Result<PathBuf> {
     Ok(sync_directory()?.join("Alfred.alfredpreferences/workflows"))
 }
 
-pub fn package(src_dir: &Path, dst: &Path) -> Result<()> {
+/// Returns the targets of broken symlinks in the Alfred workflows directory
+/// that look like they pointed to the given workflow directory before it was
+/// moved.
+///
+/// A target matches if it is inside the manifest directory, or if it ends with
+/// the name of the manifest directory followed by the workflow directory
+/// relative to it, e.g. `my-workflow/workflow`. Links to other workflows are
+/// ignored.
+pub fn stale_links(workflows_dir: &Path, manifest_dir: &Path, workflow_dir: &Path) -> Vec<PathBuf> {
+    let mut suffix = PathBuf::from(manifest_dir.file_name().unwrap_or_default());
+    suffix.push(
+        workflow_dir
+            .strip_prefix(manifest_dir)
+            .unwrap_or(workflow_dir),
+    );
+    let Ok(entries) = fs::read_dir(workflows_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_symlink()))
+        .filter(|entry| !entry.path().exists())
+        .filter_map(|entry| entry.path().read_link().ok())
+        .filter(|target| target.starts_with(manifest_dir) || target.ends_with(&suffix))
+        .collect()
+}
+
+/// Returns the cache directory Alfred uses for the workflow with the given
+/// bundle ID, i.e. the `alfred_workflow_cache` variable.
+pub fn workflow_cache_dir(bundle_id: &str) -> Result<PathBuf> {
+    let home = home::home_dir().context("failed to get home directory")?;
+    Ok(home
+        .join("Library/Caches/com.runningwithcrayons.Alfred/Workflow Data")
+        .join(bundle_id))
+}
+
+/// Returns the bundle ID from the `info.plist` in the given workflow
+/// directory, if it can be determined.
+pub fn bundle_id(workflow_dir: &Path) -> Option<String> {
+    plist::Value::from_file(workflow_dir.join("info.plist"))
+        .ok()?
+        .into_dictionary()?
+        .remove("bundleid")?
+        .into_string()
+        .filter(|id| !id.trim().is_empty())
+}
+
+/// Returns the workflow variables from the `info.plist` in the given workflow
+/// directory.
+pub fn variables(workflow_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let path = workflow_dir.join("info.plist");
+    let info = read_info(&path)?;
+    let Some(variables) = info.get("variables") else {
+        return Ok(BTreeMap::new());
+    };
+    variables
+        .as_dictionary()
+        .with_context(|| {
+            format!(
+                "expected `variables` to be a dictionary in `{}`",
+                path.display()
+            )
+        })?
+        .iter()
+        .map(|(key, value)| {
+            let value = value.as_string().with_context(|| {
+                format!(
+                    "expected variable `{key}` to be a string in `{}`",
+                    path.display()
+                )
+            })?;
+            Ok((key.clone(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// Set a workflow variable in the `info.plist` in the given workflow
+/// directory, returning the previous value.
+pub fn set_variable(workflow_dir: &Path, key: &str, value: &str) -> Result<Option<String>> {
+    let path = workflow_dir.join("info.plist");
+    let mut info = read_info(&path)?;
+    if !info.contains_key("variables") {
+        info.insert("variables".to_owned(), plist::Dictionary::new().into());
+    }
+    let variables = info
+        .get_mut("variables")
+        .and_then(plist::Value::as_dictionary_mut)
+        .with_context(|| {
+            format!(
+                "expected `variables` to be a dictionary in `{}`",
+                path.display()
+            )
+        })?;
+    let previous = variables
+        .insert(key.to_owned(), value.into())
+        .and_then(plist::Value::into_string);
+    plist::Value::from(info).to_file_xml(&path)?;
+    Ok(previous)
+}
+
+/// Check that the `info.plist` in the given workflow directory is valid and
+/// has the keys that Alfred requires to import the workflow.
+pub fn validate(workflow_dir: &Path) -> Result<()> {
+    let path = workflow_dir.join("info.plist");
+    let info = read_info(&path)?;
+    for key in ["bundleid", "name"] {
+        match info.get(key).and_then(plist::Value::as_string) {
+            Some(value) if !value.trim().is_empty() => {}
+            Some(_) => bail!("`{key}` is empty in `{}`", path.display()),
+            None => bail!(
+                "expected `{key}` to be a string in `{}`\n\n\
+                 hint: set it in Alfred Preferences under the workflow configuration",
+                path.display()
+            ),
+        }
+    }
+    if info
+        .get("objects")
+        .and_then(plist::Value::as_array)
+        .is_none()
+    {
+        bail!("expected `objects` to be an array in `{}`", path.display());
+    }
+    Ok(())
+}
+
+fn read_info(path: &Path) -> Result<plist::Dictionary> {
+    plist::Value::from_file(path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?
+        .into_dictionary()
+        .with_context(|| format!("expected dictionary in `{}`", path.display()))
+}
+
+/// Open Alfred Preferences at the given workflow, or at the workflows pane if
+/// no bundle ID is given.
+pub fn open_preferences(bundle_id: Option<&str>) -> Result<()> {
+    let url = match bundle_id {
+        Some(id) => format!("alfredpreferences://navigateto/workflows>workflow>{id}"),
+        None => "alfredpreferences://navigateto/workflows".to_owned(),
+    };
+    let status = process::Command::new("open")
+        .arg(&url)
+        .status()
+        .context("failed to run `open`")?;
+    if !status.success() {
+        bail!("failed to open `{url}`");
+    }
+    Ok(())
+}
+
+/// Run an AppleScript using `osascript`, returning its trimmed output.
+fn osascript(script: &str) -> Result<String> {
+    let output = process::Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .output()
+        .context("failed to run `osascript`")?;
+    if !output.status.success() {
+        bail!(
+            "`osascript` did not exit successfully\n\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Tell Alfred to reload the workflow with the given bundle ID.
+///
+/// This runs the following AppleScript, which requires Alfred 4.5 or later.
+/// ```applescript
+/// tell application id "com.runningwithcrayons.Alfred" to reload workflow "<bundleid>"
+/// ```
+///
+/// Returns `false` without doing anything if Alfred is not running, since
+/// telling it to reload would launch it.
+pub fn reload(bundle_id: &str) -> Result<bool> {
+    const APP: &str = r#"application id "com.runningwithcrayons.Alfred""#;
+    if osascript(&format!("{APP} is running"))? != "true" {
+        return Ok(false);
+    }
+    let bundle_id = bundle_id.replace('\\', "\\\\").replace('"', "\\\"");
+    osascript(&format!(r#"tell {APP} to reload workflow "{bundle_id}""#))?;
+    Ok(true)
+}
+
+/// Zip up the workflow directory into an `.alfredworkflow` file.
+///
+/// Files are deflated using the given `compression` level from 0 to 9, the
+/// archive must stay a plain zip file for Alfred to be able to install it.
+///
+/// Each entry is given a modification time according to `timestamp`, and
+/// entries are always written in order of their path.
+///
+/// `progress` is called after each entry is written with the number of
+/// entries written so far and the total number of entries.
+pub fn package(
+    src_dir: &Path,
+    dst: &Path,
+    compression: u32,
+    timestamp: Timestamp,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<()> {
     let file = fs::File::create(dst)?;
     let mut zip = zip::ZipWriter::new(file);
 
-    for entry in src_dir.read_dir()? {
-        let entry = entry?;
+    let mut entries = src_dir.read_dir()?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    let total = entries.len();
+    for (i, entry) in entries.into_iter().enumerate() {
         let path = entry.path();
         let name = path.strip_prefix(src_dir).unwrap().to_str().unwrap();
 
         // preserve file permissions
         let mode = path.metadata()?.permissions().mode();
-        let options = zip::write::FileOptions::default().unix_permissions(mode);
+        let mut options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(Some(compression as i32))
+            .unix_permissions(mode);
+        if timestamp == Timestamp::Fixed {
+            options = options.last_modified_time(zip::DateTime::default());
+        }
 
         if path.is_file() {
             zip.start_file(name, options)?;
@@ -137,6 +519,7 @@ pub fn package(src_dir: &Path, dst: &Path) -> Result<()> {
         } else {
             zip.add_directory(name, options)?;
         }
+        progress(i + 1, total);
     }
     zip.finish()?;
     Ok(())
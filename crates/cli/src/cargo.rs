@@ -1,4 +1,5 @@
-use std::ffi::OsStr;
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process;
@@ -6,6 +7,7 @@ use std::str::FromStr;
 
 use anyhow::{bail, Context, Result};
 pub use cargo_metadata as metadata;
+use serde::Deserialize;
 use toml_edit as toml;
 
 #[derive(Debug)]
@@ -19,13 +21,37 @@ pub enum Mode {
     Release,
 }
 
+/// The default workflow directory, relative to the package manifest.
+const DEFAULT_WORKFLOW_DIR: &str = "workflow";
+
+/// Workflow configuration from the `[package.metadata.powerpack]` table in the
+/// Cargo manifest.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The workflow directory, relative to the manifest directory.
+    pub workflow_dir: Option<PathBuf>,
+    /// The workflow bundle ID.
+    pub bundle_id: Option<String>,
+    /// Workflow environment variables.
+    pub variables: BTreeMap<String, String>,
+    /// Workflow environment variables that are not exported with the
+    /// workflow, e.g. API tokens.
+    pub variables_dont_export: Vec<String>,
+    /// A shell command to run after the binaries are copied to the workflow
+    /// directory.
+    pub post_build: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Metadata {
-    pub workspace_dir: PathBuf,
     pub manifest_dir: PathBuf,
+    pub workflow_dir: PathBuf,
     pub target_dir: PathBuf,
     pub package_name: String,
+    pub package_version: String,
     pub binary_names: Vec<String>,
+    pub post_build: Option<String>,
 }
 
 impl Cargo {
@@ -78,6 +104,7 @@ pub fn build(
     package: Option<&str>,
     bins: &[String],
     target: Option<&str>,
+    args: &[OsString],
 ) -> Result<()> {
     let mut cmd = Cargo::new("build");
     if let Some(package) = package {
@@ -94,29 +121,102 @@ pub fn build(
         cmd.arg("--target");
         cmd.arg(target);
     }
+    for arg in args {
+        cmd.arg(arg);
+    }
     cmd.run()
 }
 
+/// Returns the host target triple according to `rustc`.
+pub fn host_target() -> Option<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = process::Command::new(rustc).arg("-vV").output().ok()?;
+    String::from_utf8(output.stdout)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_owned)
+}
+
+/// Check that the given target is installed using `rustup`.
+///
+/// The check is skipped if `rustup` is not available or the target is a
+/// custom target specification file.
+pub fn check_target(target: &str) -> Result<()> {
+    if target.ends_with(".json") {
+        return Ok(());
+    }
+    let Ok(output) = process::Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .stderr(process::Stdio::null())
+        .output()
+    else {
+        return Ok(());
+    };
+    if !output.status.success() {
+        return Ok(());
+    }
+    let installed = String::from_utf8_lossy(&output.stdout);
+    if !installed.lines().any(|line| line.trim() == target) {
+        bail!(
+            "target `{target}` is not installed\n\n\
+             hint: install it using `rustup target add {target}`"
+        );
+    }
+    Ok(())
+}
+
 /// Run a `cargo metadata` command.
-pub fn metadata(package: Option<&str>) -> Result<Metadata> {
+///
+/// Any of `--offline`, `--locked`, or `--frozen` in `args`, the extra arguments
+/// given for `cargo build`, are passed on so that they apply to resolving the
+/// metadata as well.
+pub fn metadata(package: Option<&str>, args: &[OsString]) -> Result<Metadata> {
+    let options: Vec<_> = args
+        .iter()
+        .filter_map(|arg| arg.to_str())
+        .filter(|arg| matches!(*arg, "--offline" | "--locked" | "--frozen"))
+        .map(str::to_owned)
+        .collect();
     let metadata::Metadata {
         packages,
-        workspace_root,
+        workspace_members,
         target_directory,
         resolve,
         ..
-    } = metadata::MetadataCommand::new().exec()?;
-
-    let pkg = match package {
-        Some(n) => packages
-            .into_iter()
-            .find(|pkg| pkg.name == n)
-            .with_context(|| format!("package not found: `{}`", n))?,
-        None => (move || {
-            let root = resolve.as_ref()?.root.as_ref()?;
-            packages.into_iter().find(|pkg| &pkg.id == root)
-        })()
-        .context("no root package")?,
+    } = metadata::MetadataCommand::new()
+        .other_options(options)
+        .exec()?;
+
+    let root = resolve.and_then(|resolve| resolve.root);
+    let members = || {
+        packages
+            .iter()
+            .filter(|pkg| workspace_members.contains(&pkg.id))
+            .map(|pkg| format!("`{}`", pkg.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let pkg = match (package, root) {
+        (Some(n), _) => match packages.iter().position(|pkg| pkg.name == n) {
+            Some(i) => packages[i].clone(),
+            None => bail!(
+                "package `{n}` not found in workspace\n\n\
+                 hint: available packages are {}",
+                members()
+            ),
+        },
+        (None, Some(root)) => packages
+            .iter()
+            .find(|pkg| pkg.id == root)
+            .cloned()
+            .context("no root package")?,
+        (None, None) => bail!(
+            "could not determine which package to use, this is a virtual \
+             workspace\n\n\
+             hint: use `--package` to choose one of {}",
+            members()
+        ),
     };
 
     let binary_names = pkg
@@ -126,15 +226,82 @@ pub fn metadata(package: Option<&str>) -> Result<Metadata> {
         .map(|target| target.name)
         .collect();
 
+    let config = match pkg.metadata.get("powerpack") {
+        Some(value) => Config::deserialize(value)
+            .context("failed to parse `[package.metadata.powerpack]` table")?,
+        None => Config::default(),
+    };
+    let manifest_dir = pkg.manifest_path.parent().unwrap().as_std_path();
+    let workflow_dir = manifest_dir.join(config.workflow_dir());
+
     Ok(Metadata {
-        workspace_dir: workspace_root.into(),
-        manifest_dir: pkg.manifest_path.parent().unwrap().into(),
+        manifest_dir: manifest_dir.to_owned(),
+        workflow_dir,
         target_dir: target_directory.into(),
         package_name: pkg.name,
+        package_version: pkg.version.to_string(),
         binary_names,
+        post_build: config.post_build,
     })
 }
 
+impl Config {
+    /// Returns the workflow directory, relative to the manifest directory.
+    pub fn workflow_dir(&self) -> &Path {
+        self.workflow_dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new(DEFAULT_WORKFLOW_DIR))
+    }
+}
+
+/// Read the workflow configuration from a Cargo manifest.
+pub fn config(doc: &toml::Document) -> Result<Config> {
+    match doc
+        .get("package")
+        .and_then(|t| t.get("metadata"))
+        .and_then(|t| t.get("powerpack"))
+    {
+        Some(item) => Config::deserialize(toml_to_json(item))
+            .context("failed to parse `[package.metadata.powerpack]` table"),
+        None => Ok(Config::default()),
+    }
+}
+
+/// Convert a TOML item to JSON so that it can be deserialized.
+fn toml_to_json(item: &toml::Item) -> serde_json::Value {
+    use serde_json::Value;
+
+    fn convert(value: &toml::Value) -> Value {
+        match value {
+            toml::Value::String(s) => Value::from(s.value().as_str()),
+            toml::Value::Integer(i) => Value::from(*i.value()),
+            toml::Value::Float(f) => Value::from(*f.value()),
+            toml::Value::Boolean(b) => Value::from(*b.value()),
+            toml::Value::Datetime(d) => Value::from(d.value().to_string()),
+            toml::Value::Array(a) => a.iter().map(convert).collect(),
+            toml::Value::InlineTable(t) => t
+                .iter()
+                .map(|(k, v)| (k.to_owned(), convert(v)))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        }
+    }
+
+    match item {
+        toml::Item::None => Value::Null,
+        toml::Item::Value(value) => convert(value),
+        toml::Item::Table(t) => t
+            .iter()
+            .map(|(k, v)| (k.to_owned(), toml_to_json(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        toml::Item::ArrayOfTables(a) => a
+            .iter()
+            .map(|t| toml_to_json(&toml::Item::Table(t.clone())))
+            .collect(),
+    }
+}
+
 /// Read the Cargo manifest.
 pub fn read_manifest(dir: &Path) -> Result<toml::Document> {
     let manifest_path = dir.join("Cargo.toml");
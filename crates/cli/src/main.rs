@@ -1,5 +1,6 @@
 mod alfred;
 mod cargo;
+mod sign;
 
 use std::env;
 use std::ffi::OsString;
@@ -8,65 +9,216 @@ use std::fs;
 use std::io;
 use std::io::prelude::*;
 use std::io::IsTerminal;
-use std::os::unix::fs::symlink;
+use std::os::unix::fs::{symlink, PermissionsExt};
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{ColorChoice, Parser};
 use peter::Stylize;
 use toml_edit as toml;
 
+/// Whether to style output written to the given stream.
+///
+/// Styling is disabled if the stream is not a terminal, if `NO_COLOR` is set
+/// to a non-empty value, or if `TERM` is `dumb`.
+fn use_color(stream: &impl IsTerminal) -> bool {
+    stream.is_terminal()
+        && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && env::var_os("TERM").is_none_or(|v| v != "dumb")
+}
+
+/// Whether `--dry-run` was given, in which case nothing should be changed.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Whether status messages should be suppressed because stdout is used for
+/// machine readable output, e.g. the output of `powerpack run` or the
+/// `powerpack build --json` manifest.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
 fn print(header: &str, message: impl AsRef<str>) {
-    if io::stdout().is_terminal() {
-        println!("{:>12} {}", header.bold().green(), message.as_ref());
+    if is_quiet() {
+        return;
+    }
+    let suffix = if is_dry_run() { " (dry run)" } else { "" };
+    if use_color(&io::stdout()) {
+        println!("{:>12} {}{suffix}", header.bold().green(), message.as_ref());
     } else {
-        println!("{:>12} {}", header, message.as_ref());
+        println!("{:>12} {}{suffix}", header, message.as_ref());
     }
 }
 
 fn print_warning(header: &str, message: impl AsRef<str>) {
-    if io::stdout().is_terminal() {
+    if use_color(&io::stderr()) {
         eprintln!("{:>12} {}", header.bold().yellow(), message.as_ref());
     } else {
         eprintln!("{:>12} {}", header, message.as_ref());
     }
 }
 
-fn prompt_for_workflow_info(doc: &toml::Document) -> Result<alfred::WorkflowInfo> {
+/// Print a progress line to stderr that is overwritten by the next one.
+///
+/// Progress relies on terminal escape codes so it is only shown if stderr is
+/// styled, see [`use_color`].
+fn print_progress(header: &str, message: impl AsRef<str>) {
+    if use_color(&io::stderr()) {
+        eprint!("\r{:>12} {}", header.bold().cyan(), message.as_ref());
+    }
+}
+
+/// Clear the current progress line, if progress is shown.
+fn clear_progress() {
+    if use_color(&io::stderr()) {
+        eprint!("\r\x1b[2K");
+    }
+}
+
+/// Returns a string field from the `[package]` table of a Cargo manifest.
+fn manifest_str<'a>(doc: &'a toml::Document, key: &str) -> Option<&'a str> {
+    doc.get("package")
+        .and_then(|t| t.get(key))
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+}
+
+/// Prompt for a value, accepting the given default on empty input.
+fn prompt_with_default(text: &str, default: Option<&str>) -> String {
+    match default {
+        Some(default) => casual::prompt(format!("{text} [{default}]: "))
+            .default(default.to_owned())
+            .get(),
+        None => casual::prompt(format!("{text}: ")).get(),
+    }
+}
+
+fn prompt_for_workflow_info(
+    doc: &toml::Document,
+    config: cargo::Config,
+    argument_type: alfred::ArgumentType,
+) -> Result<alfred::WorkflowInfo> {
     let package_name = doc["package"]["name"].as_str().context("expected string")?;
+    // Cargo authors are of the form "Name <email>", Alfred only needs the names
+    let author = doc
+        .get("package")
+        .and_then(|t| t.get("authors"))
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str())
+                .map(|a| a.split('<').next().unwrap().trim())
+                .filter(|a| !a.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .filter(|a| !a.is_empty());
+    for key in &config.variables_dont_export {
+        if !config.variables.contains_key(key) {
+            print_warning(
+                "Warning",
+                format!("`variables_dont_export` contains unknown variable `{key}`"),
+            );
+        }
+    }
     println!("Please enter the workflow details:");
     Ok(alfred::WorkflowInfo {
         name: package_name.to_owned(),
         bin_name: package_name.to_owned(),
-        bundle_id: casual::prompt("Bundle ID: ").get(),
-        author: casual::prompt("Author: ").get(),
-        description: casual::prompt("Description: ").get(),
+        bundle_id: match config.bundle_id {
+            Some(bundle_id) => bundle_id,
+            None => casual::prompt("Bundle ID: ").get(),
+        },
+        author: prompt_with_default("Author", author.as_deref()),
+        description: prompt_with_default("Description", manifest_str(doc, "description")),
+        version: prompt_with_default("Version", manifest_str(doc, "version")),
+        category: prompt_with_default("Category", Some("Tools")),
         keyword: casual::prompt("Keyword: ").get(),
+        argument_type,
+        readme: manifest_str(doc, "description")
+            .unwrap_or_default()
+            .to_owned(),
+        web_address: match manifest_str(doc, "homepage").or(manifest_str(doc, "repository")) {
+            Some(url) => url.to_owned(),
+            None => casual::prompt("Website (optional): ")
+                .default(String::new())
+                .get(),
+        },
+        variables: config.variables,
+        variables_dont_export: config.variables_dont_export,
     })
 }
 
 /// Create a new Alfred workflow in the given directory.
-fn init(manifest_dir: &Path, name: Option<OsString>) -> Result<()> {
-    cargo::init(manifest_dir, name)?;
+fn init(
+    manifest_dir: &Path,
+    name: Option<OsString>,
+    bundle_id: Option<String>,
+    argument_type: alfred::ArgumentType,
+    icon: Option<&Path>,
+) -> Result<()> {
+    // Only create a new Cargo package if there isn't one already
+    let existing = manifest_dir.join("Cargo.toml").exists();
+    if existing {
+        print("Found", "existing Cargo package, skipping `cargo init`");
+    } else {
+        cargo::init(manifest_dir, name)?;
+    }
     let doc = cargo::read_manifest(manifest_dir).context("failed to read Cargo manifest")?;
     let package_name = doc["package"]["name"].as_str().context("expected string")?;
+    let mut config = cargo::config(&doc)?;
+    if bundle_id.is_some() {
+        config.bundle_id = bundle_id;
+    }
+    let workflow_dir_name = config.workflow_dir().to_owned();
+    let workflow_dir = manifest_dir.join(&workflow_dir_name);
 
     // Write the info.plist file
-    let info = prompt_for_workflow_info(&doc)?;
-    let info = alfred::build_info_plist(&info);
-    let workflow_dir = manifest_dir.join("workflow");
-    fs::create_dir_all(&workflow_dir)?;
-    info.to_file_xml(workflow_dir.join("info.plist"))?;
-
-    // Add workflow/<binary> to the gitignore file (if it exists)
-    if let Ok(mut file) = fs::OpenOptions::new()
-        .append(true)
-        .open(manifest_dir.join(".gitignore"))
-    {
-        writeln!(file, "/workflow/{package_name}")?;
+    let info_path = workflow_dir.join("info.plist");
+    if info_path.exists() {
+        print_warning(
+            "Skipped",
+            format!("writing `{}`, it already exists", display_path(&info_path)),
+        );
+    } else {
+        let info = prompt_for_workflow_info(&doc, config, argument_type)?;
+        let info = alfred::build_info_plist(&info);
+        fs::create_dir_all(&workflow_dir)?;
+        info.to_file_xml(&info_path)?;
     }
 
-    // Add dependencies to Cargo manifest.
+    // Write the workflow icon, an explicitly given icon replaces any existing
+    let icon_path = workflow_dir.join("icon.png");
+    match icon {
+        Some(icon) => {
+            fs::copy(icon, &icon_path)
+                .with_context(|| format!("failed to copy icon `{}`", icon.display()))?;
+        }
+        None if icon_path.exists() => {}
+        None => fs::write(&icon_path, include_bytes!("icon.png"))?,
+    }
+
+    // Add workflow/<binary> and the packaged artifacts to the gitignore file
+    update_gitignore(
+        &manifest_dir.join(".gitignore"),
+        &[
+            format!("/{}/{package_name}", workflow_dir_name.display()),
+            "/target/workflow/".to_owned(),
+        ],
+    )?;
+
+    // Add dependencies to Cargo manifest, keeping any existing requirement
+    if doc
+        .get("dependencies")
+        .and_then(|t| t.get("powerpack"))
+        .is_none()
     {
         let mut doc = doc;
         let table = &mut doc["dependencies"];
@@ -74,11 +226,65 @@ fn init(manifest_dir: &Path, name: Option<OsString>) -> Result<()> {
         cargo::write_manifest(manifest_dir, &doc)?;
     }
 
-    // Write our custom `main.rs`
+    // Write our custom `main.rs`, asking first if it would replace user code
+    let template = include_str!("main.template.rs");
     let main = manifest_dir.join("src").join("main.rs");
-    fs::write(main, include_str!("main.template.rs"))?;
-    print("Finished", "created example script filter workflow");
+    let overwrite = !existing
+        || !main.exists()
+        || fs::read_to_string(&main).is_ok_and(|contents| contents == template)
+        || casual::confirm(format!(
+            "Overwrite `{}` with the example workflow?",
+            display_path(&main)
+        ));
+    if overwrite {
+        fs::create_dir_all(main.parent().unwrap())?;
+        fs::write(&main, template)?;
+        print("Finished", "created example script filter workflow");
+    } else {
+        print_warning("Skipped", format!("writing `{}`", display_path(&main)));
+        print("Finished", "initialized workflow");
+    }
+
+    Ok(())
+}
+
+/// Add the given patterns to a gitignore file, creating it if necessary.
+///
+/// Patterns that are already present are not added again, and neither is a
+/// pattern inside the target directory if the whole directory is ignored.
+fn update_gitignore(path: &Path, patterns: &[String]) -> Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).context("failed to read `.gitignore`"),
+    };
+    let existing: Vec<_> = contents.lines().map(str::trim).collect();
+    let target_ignored = ["target", "target/", "/target", "/target/"]
+        .iter()
+        .any(|p| existing.contains(p));
+
+    let mut new = String::new();
+    for pattern in patterns {
+        if existing.contains(&pattern.as_str())
+            || (target_ignored && pattern.starts_with("/target/"))
+        {
+            continue;
+        }
+        new.push_str(pattern);
+        new.push('\n');
+    }
+    if new.is_empty() {
+        return Ok(());
+    }
 
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        writeln!(file)?;
+    }
+    file.write_all(new.as_bytes())?;
     Ok(())
 }
 
@@ -88,17 +294,29 @@ fn build(
     bins: Vec<String>,
     release: bool,
     target: Option<&str>,
+    args: &[OsString],
+    post_build: Option<&str>,
+    json: bool,
 ) -> Result<()> {
     let mode = if release {
         cargo::Mode::Release
     } else {
         cargo::Mode::Debug
     };
-    cargo::build(mode, package, &bins, target)?;
+    let metadata = cargo::metadata(package, args)?;
+    if let Some(target) = target {
+        cargo::check_target(target)?;
+    }
+    if is_dry_run() {
+        print("Building", format!("package `{}`", metadata.package_name));
+    } else {
+        cargo::build(mode, package, &bins, target, args)?;
+    }
 
-    let metadata = cargo::metadata(package)?;
-    let workflow_dir = metadata.manifest_dir.join("workflow");
-    fs::create_dir_all(&workflow_dir)?;
+    let workflow_dir = metadata.workflow_dir;
+    if !is_dry_run() {
+        fs::create_dir_all(&workflow_dir)?;
+    }
 
     let src_dir = match target {
         Some(target) => metadata.target_dir.join(target).join(mode.dir()),
@@ -116,31 +334,219 @@ fn build(
             "Warning",
             format!("package `{}` has no binaries", metadata.package_name),
         );
+        if json {
+            print_build_json(
+                &metadata.package_name,
+                &metadata.package_version,
+                target,
+                mode,
+                &[],
+            );
+        }
         return Ok(());
     }
 
+    let mut binaries = Vec::new();
     for binary_name in &binary_names {
         let src = src_dir.join(binary_name);
         let dst = workflow_dir.join(binary_name);
-        let removed = fs::remove_file(&dst).is_ok();
-        fs::copy(src, &dst)?;
+        let (removed, size) = if is_dry_run() {
+            (dst.exists(), src.metadata().map_or(0, |m| m.len()))
+        } else {
+            (fs::remove_file(&dst).is_ok(), fs::copy(src, &dst)?)
+        };
 
         if removed {
             print("Replaced", format!("binary at `{}`", display_path(&dst)));
         } else {
             print("Copied", format!("binary to `{}`", display_path(&dst)));
         }
+        binaries.push((binary_name.as_str(), dst, size));
+    }
+
+    if let Some(cmd) = post_build.or(metadata.post_build.as_deref()) {
+        run_post_build(cmd, &metadata.manifest_dir, &workflow_dir, &src_dir)?;
+    }
+
+    if json {
+        print_build_json(
+            &metadata.package_name,
+            &metadata.package_version,
+            target,
+            mode,
+            &binaries,
+        );
+    }
+
+    check_link(&metadata.manifest_dir, &workflow_dir);
+
+    Ok(())
+}
+
+/// Print a JSON description of the build to stdout.
+fn print_build_json(
+    package_name: &str,
+    package_version: &str,
+    target: Option<&str>,
+    mode: cargo::Mode,
+    binaries: &[(&str, PathBuf, u64)],
+) {
+    let target = target.map(str::to_owned).or_else(cargo::host_target);
+    let binaries: Vec<_> = binaries
+        .iter()
+        .map(|(name, path, size)| {
+            serde_json::json!({
+                "name": name,
+                "path": path,
+                "size": size,
+            })
+        })
+        .collect();
+    let manifest = serde_json::json!({
+        "package": package_name,
+        "version": package_version,
+        "target": target,
+        "profile": mode.dir(),
+        "binaries": binaries,
+    });
+    println!("{manifest}");
+}
+
+/// Run `f` for each of the given packages, or once for the default package if
+/// none are given.
+///
+/// If `keep_going` is set then a failure is reported and the remaining
+/// packages are still processed, otherwise the first error is returned.
+fn for_each_package<F>(packages: &[String], keep_going: bool, mut f: F) -> Result<()>
+where
+    F: FnMut(Option<&str>) -> Result<()>,
+{
+    if packages.is_empty() {
+        return f(None);
+    }
+    let mut failed = Vec::new();
+    for package in packages {
+        match f(Some(package)) {
+            Ok(()) => {}
+            Err(err) if keep_going => {
+                print_warning("Failed", format!("package `{package}`: {err:#}"));
+                failed.push(format!("`{package}`"));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} packages failed: {}",
+            failed.len(),
+            packages.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Run the post build shell command.
+///
+/// The command is run in the package directory with `WORKFLOW_DIR` set to
+/// the workflow directory and `TARGET_DIR` set to the directory containing the
+/// built binaries. If status messages are suppressed then the command's
+/// stdout is redirected to stderr, so that stdout only contains the build
+/// manifest or the workflow output.
+fn run_post_build(
+    cmd: &str,
+    manifest_dir: &Path,
+    workflow_dir: &Path,
+    src_dir: &Path,
+) -> Result<()> {
+    let mut command = process::Command::new("sh");
+    if is_quiet() {
+        command.stdout(io::stderr());
+    }
+    print("Running", format!("post build command `{cmd}`"));
+    if is_dry_run() {
+        return Ok(());
+    }
+    let status = command
+        .arg("-c")
+        .arg(cmd)
+        .current_dir(manifest_dir)
+        .env("WORKFLOW_DIR", workflow_dir)
+        .env("TARGET_DIR", src_dir)
+        .status()
+        .context("failed to run post build command")?;
+    if !status.success() {
+        bail!("post build command `{cmd}` did not exit successfully: {status}");
+    }
+    Ok(())
+}
+
+/// Warn if the workflow directory is not linked to Alfred but there is a
+/// broken symlink in the Alfred workflows directory that used to point to it,
+/// which usually means the workflow directory was moved after it was linked.
+///
+/// This is purely diagnostic so any errors are ignored.
+fn check_link(manifest_dir: &Path, workflow_dir: &Path) {
+    let Ok(workflows_dir) = alfred::workflows_directory() else {
+        return;
+    };
+    if !matches!(find_link(workflow_dir, &workflows_dir), Ok(None)) {
+        return;
+    }
+    let broken = alfred::stale_links(&workflows_dir, manifest_dir, workflow_dir);
+    if !broken.is_empty() {
+        print_warning(
+            "Warning",
+            format!(
+                "workflow directory is not linked to Alfred, but found broken \
+                 symlinks to {}\n\n\
+                 hint: run `powerpack link --force` to link it again",
+                broken
+                    .iter()
+                    .map(|p| format!("`{}`", p.display()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        );
     }
+}
 
+/// Check that the given binaries have been built into the workflow directory.
+fn check_binaries<'a>(
+    workflow_dir: &Path,
+    binary_names: impl IntoIterator<Item = &'a String>,
+) -> Result<()> {
+    for binary_name in binary_names {
+        let path = workflow_dir.join(binary_name);
+        let is_executable = path
+            .metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+        if !is_executable {
+            bail!(
+                "binary `{}` does not exist or is not executable\n\n\
+                 hint: run `powerpack build` first",
+                display_path(&path)
+            );
+        }
+    }
     Ok(())
 }
 
 fn find_link(workflow_dir: &Path, workflows_dir: &Path) -> Result<Option<PathBuf>> {
-    for entry in fs::read_dir(workflows_dir)?
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .filter(|entry| entry.file_type().unwrap().is_symlink())
-    {
+    let entries = match fs::read_dir(workflows_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to read directory `{}`", workflows_dir.display()))
+        }
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_symlink() {
+            continue;
+        }
         let path = entry.path();
         if path.read_link()? == workflow_dir {
             return Ok(Some(path));
@@ -150,12 +556,198 @@ fn find_link(workflow_dir: &Path, workflows_dir: &Path) -> Result<Option<PathBuf
 }
 
 /// Link the workflow.
-fn link(package: Option<&str>, force: bool) -> Result<()> {
-    let metadata = cargo::metadata(package)?;
-    let workflow_dir = metadata.manifest_dir.join("workflow");
+fn link(package: Option<&str>, force: bool, open: bool) -> Result<()> {
+    let metadata = cargo::metadata(package, &[])?;
+    let workflow_dir = metadata.workflow_dir;
+    check_binaries(&workflow_dir, &metadata.binary_names)?;
+    symlink_workflow(&workflow_dir, force)?;
+
+    if open && !is_dry_run() {
+        match alfred::bundle_id(&workflow_dir) {
+            Some(bundle_id) => alfred::open_preferences(Some(&bundle_id))?,
+            None => print_warning(
+                "Skipped",
+                "opening Alfred Preferences, no bundle ID found in `info.plist`",
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Build and run the workflow binary with the given query, like Alfred would.
+fn run(
+    package: Option<&str>,
+    bin: Option<String>,
+    query: Option<String>,
+    ndjson: bool,
+) -> Result<()> {
+    build(
+        package,
+        bin.iter().cloned().collect(),
+        false,
+        None,
+        &[],
+        None,
+        false,
+    )?;
+
+    let metadata = cargo::metadata(package, &[])?;
+    let bin = match bin {
+        Some(bin) => bin,
+        None => match metadata.binary_names.as_slice() {
+            [bin] => bin.clone(),
+            [] => bail!("package `{}` has no binaries", metadata.package_name),
+            _ => bail!(
+                "package `{}` has multiple binaries\n\n\
+                 hint: use `--bin` to choose which one to run",
+                metadata.package_name
+            ),
+        },
+    };
+
+    let workflow_dir = metadata.workflow_dir;
+    let bundle_id = alfred::bundle_id(&workflow_dir)
+        .unwrap_or_else(|| format!("powerpack.{}", metadata.package_name));
+    let tmp_dir = env::temp_dir().join("powerpack").join(&bundle_id);
+
+    let output = process::Command::new(workflow_dir.join(&bin))
+        .args(query)
+        .current_dir(&workflow_dir)
+        .env("alfred_workflow_bundleid", &bundle_id)
+        .env("alfred_workflow_name", &metadata.package_name)
+        .env("alfred_workflow_cache", tmp_dir.join("cache"))
+        .env("alfred_workflow_data", tmp_dir.join("data"))
+        .env("alfred_debug", "1")
+        .stdin(process::Stdio::null())
+        .stderr(process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run `{bin}`"))?;
+    if !output.status.success() {
+        print_warning(
+            "Warning",
+            format!("`{bin}` did not exit successfully: {}", output.status),
+        );
+    }
+
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("`{bin}` did not output valid JSON"))?;
+    if ndjson {
+        let items = value.get("items").and_then(|items| items.as_array());
+        for item in items.into_iter().flatten() {
+            println!("{item}");
+        }
+    } else {
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    }
+    Ok(())
+}
+
+/// Get or set workflow variables in the workflow's `info.plist`.
+fn config(package: Option<&str>, action: ConfigAction) -> Result<()> {
+    let metadata = cargo::metadata(package, &[])?;
+    let workflow_dir = &metadata.workflow_dir;
+    match action {
+        ConfigAction::Set { key, value } => {
+            match alfred::set_variable(workflow_dir, &key, &value)? {
+                Some(previous) if previous == value => {
+                    print("Unchanged", format!("variable `{key}`"));
+                }
+                Some(_) => print("Updated", format!("variable `{key}`")),
+                None => print("Added", format!("variable `{key}`")),
+            }
+        }
+        ConfigAction::Get { key } => {
+            let mut variables = alfred::variables(workflow_dir)?;
+            let value = variables.remove(&key).with_context(|| {
+                format!(
+                    "variable `{key}` not found in `{}`",
+                    display_path(&workflow_dir.join("info.plist"))
+                )
+            })?;
+            println!("{value}");
+        }
+        ConfigAction::List => {
+            for (key, value) in alfred::variables(workflow_dir)? {
+                println!("{key}={value}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print, follow, or clear a log file in the workflow's cache directory.
+fn logs(package: Option<&str>, file: Option<&Path>, follow: bool, clear: bool) -> Result<()> {
+    let metadata = cargo::metadata(package, &[])?;
+    let bundle_id = alfred::bundle_id(&metadata.workflow_dir).with_context(|| {
+        format!(
+            "no bundle ID found in `{}`",
+            metadata.workflow_dir.join("info.plist").display()
+        )
+    })?;
+    let cache_dir = alfred::workflow_cache_dir(&bundle_id)?;
+
+    let path = match file {
+        Some(file) => cache_dir.join(file),
+        None => {
+            let mut logs: Vec<_> = fs::read_dir(&cache_dir)
+                .with_context(|| format!("failed to read `{}`", cache_dir.display()))?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+                .collect();
+            match logs.len() {
+                1 => logs.remove(0),
+                0 => bail!("no log files found in `{}`", cache_dir.display()),
+                _ => bail!(
+                    "multiple log files found in `{}`\n\n\
+                     hint: use `--file` to choose one",
+                    cache_dir.display()
+                ),
+            }
+        }
+    };
+
+    if clear {
+        fs::write(&path, "").with_context(|| format!("failed to clear `{}`", path.display()))?;
+        print("Cleared", format!("log file `{}`", path.display()));
+    } else if follow {
+        let status = process::Command::new("tail")
+            .arg("-f")
+            .arg(&path)
+            .status()
+            .context("failed to run `tail`")?;
+        if !status.success() {
+            bail!("`tail` did not exit successfully");
+        }
+    } else {
+        let contents =
+            fs::read(&path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        io::stdout().write_all(&contents)?;
+    }
+    Ok(())
+}
+
+/// Tell Alfred to reload the workflow.
+fn reload(package: Option<&str>) -> Result<()> {
+    let metadata = cargo::metadata(package, &[])?;
+    let bundle_id = alfred::bundle_id(&metadata.workflow_dir).with_context(|| {
+        format!(
+            "no bundle ID found in `{}`",
+            metadata.workflow_dir.join("info.plist").display()
+        )
+    })?;
+    if is_dry_run() || alfred::reload(&bundle_id)? {
+        print("Reloaded", format!("workflow `{bundle_id}`"));
+    } else {
+        print_warning("Skipped", "reloading workflow, Alfred is not running");
+    }
+    Ok(())
+}
+
+/// Symlink the workflow directory to the Alfred workflows directory.
+fn symlink_workflow(workflow_dir: &Path, force: bool) -> Result<()> {
     let workflows_dir = alfred::workflows_directory()?;
 
-    if let Some(path) = find_link(&workflow_dir, &workflows_dir)? {
+    if let Some(path) = find_link(workflow_dir, &workflows_dir)? {
         if !force {
             print(
                 "Symlinked",
@@ -163,16 +755,32 @@ fn link(package: Option<&str>, force: bool) -> Result<()> {
             );
             return Ok(());
         }
-        fs::remove_file(&path)?;
+        if !is_dry_run() {
+            fs::remove_file(&path)?;
+        }
         print(
             "Removed",
             format!("existing symlink at `{}`", path.display()),
         );
     }
 
+    if !workflows_dir.exists() {
+        if !is_dry_run() {
+            fs::create_dir_all(&workflows_dir).with_context(|| {
+                format!("failed to create directory `{}`", workflows_dir.display())
+            })?;
+        }
+        print(
+            "Created",
+            format!("Alfred workflows directory `{}`", workflows_dir.display()),
+        );
+    }
+
     let uid = uuid::Uuid::new_v4().to_string().to_uppercase();
     let dst = workflows_dir.join(format!("user.workflow.{uid}"));
-    symlink(&workflow_dir, &dst)?;
+    if !is_dry_run() {
+        symlink(workflow_dir, &dst)?;
+    }
     print(
         "Symlinked",
         format!("workflow directory to `{}`", dst.display()),
@@ -181,21 +789,61 @@ fn link(package: Option<&str>, force: bool) -> Result<()> {
 }
 
 /// Package the workflow into a `.alfredworkflow` file.
-fn build_package(package: Option<&str>) -> Result<()> {
-    let metadata = cargo::metadata(package)?;
-    let workflow_dir = metadata.manifest_dir.join("workflow");
+fn build_package(
+    package: Option<&str>,
+    bins: &[String],
+    sign: Option<&str>,
+    notarize: Option<&str>,
+    compression: u32,
+    timestamp: alfred::Timestamp,
+    args: &[OsString],
+) -> Result<()> {
+    let metadata = cargo::metadata(package, args)?;
+    let workflow_dir = metadata.workflow_dir;
     let dist_dir = metadata.target_dir.join("workflow");
     let mut package_name = metadata.package_name;
 
+    let binary_names: Vec<_> = metadata
+        .binary_names
+        .iter()
+        .filter(|binary_name| bins.is_empty() || bins.contains(binary_name))
+        .collect();
+    alfred::validate(&workflow_dir)?;
+
+    // The binaries won't have been copied if the build was a dry run.
+    if !is_dry_run() {
+        check_binaries(&workflow_dir, binary_names.iter().copied())?;
+        fs::create_dir_all(&dist_dir)?;
+    }
+
+    if let Some(identity) = sign {
+        for binary_name in &binary_names {
+            let path = workflow_dir.join(binary_name);
+            if !is_dry_run() {
+                sign::codesign(&path, identity)?;
+            }
+            print("Signed", format!("binary at `{}`", display_path(&path)));
+            if let Some(profile) = notarize {
+                if !is_dry_run() {
+                    sign::notarize(&path, &dist_dir, profile)?;
+                }
+                print("Notarized", format!("binary at `{}`", display_path(&path)));
+            }
+        }
+    }
+
     // Just a hack because I tend to suffix my workflows with this.
     if let Some(new) = package_name.strip_suffix("-alfred-workflow") {
         package_name = new.to_owned();
     }
 
     let dst = &dist_dir.join(package_name).with_extension("alfredworkflow");
-
-    fs::create_dir_all(&dist_dir)?;
-    alfred::package(&workflow_dir, dst)?;
+    if !is_dry_run() {
+        alfred::package(&workflow_dir, dst, compression, timestamp, |done, total| {
+            print_progress("Packaging", format!("[{done}/{total}] files"));
+        })?;
+        clear_progress();
+    }
     print("Packaged", format!("workflow at `{}`", display_path(dst)));
 
     Ok(())
@@ -218,6 +866,22 @@ enum Command {
         /// Set the resulting package name, defaults to the directory name.
         #[clap(long)]
         name: Option<OsString>,
+
+        /// Set the workflow bundle ID instead of prompting for it.
+        #[clap(long, value_name = "ID")]
+        bundle_id: Option<String>,
+
+        /// Whether the script filter takes an argument.
+        #[clap(long, value_name = "TYPE", default_value = "optional")]
+        argument: alfred::ArgumentType,
+
+        /// Use the PNG file at this path as the workflow icon.
+        #[clap(long, value_name = "PATH")]
+        icon: Option<PathBuf>,
+
+        /// Open the Alfred Preferences workflows pane afterwards.
+        #[clap(long)]
+        open: bool,
     },
 
     /// Create a new Rust alfred workflow in an existing directory [default: .]
@@ -227,13 +891,33 @@ enum Command {
         /// Set the resulting package name, defaults to the directory name.
         #[clap(long)]
         name: Option<OsString>,
+
+        /// Set the workflow bundle ID instead of prompting for it.
+        #[clap(long, value_name = "ID")]
+        bundle_id: Option<String>,
+
+        /// Whether the script filter takes an argument.
+        #[clap(long, value_name = "TYPE", default_value = "optional")]
+        argument: alfred::ArgumentType,
+
+        /// Use the PNG file at this path as the workflow icon.
+        #[clap(long, value_name = "PATH")]
+        icon: Option<PathBuf>,
+
+        /// Open the Alfred Preferences workflows pane afterwards.
+        #[clap(long)]
+        open: bool,
     },
 
     /// Build the workflow.
     Build {
-        /// Package to build.
+        /// Package to build, can be given multiple times.
         #[clap(long, short, value_name = "SPEC")]
-        package: Option<String>,
+        package: Vec<String>,
+
+        /// Continue with the remaining packages if one fails.
+        #[clap(long)]
+        keep_going: bool,
 
         /// Build only the specified binary.
         #[clap(long, value_name = "NAME")]
@@ -246,6 +930,24 @@ enum Command {
         /// Build for the target triple.
         #[clap(long, value_name = "TRIPLE")]
         target: Option<String>,
+
+        /// Tell Alfred to reload the workflow afterwards.
+        #[clap(long)]
+        reload: bool,
+
+        /// Run this shell command after copying the binaries to the workflow
+        /// directory, overrides `post_build` in the Cargo manifest.
+        #[clap(long, value_name = "CMD")]
+        post_build: Option<String>,
+
+        /// Print a JSON description of the built binaries to stdout instead
+        /// of the usual messages, one line per package.
+        #[clap(long, conflicts_with = "reload")]
+        json: bool,
+
+        /// Extra arguments to pass to `cargo build`, e.g. `-- --offline`.
+        #[clap(last = true, value_name = "ARGS")]
+        args: Vec<OsString>,
     },
 
     /// Symlink the workflow directory to the Alfred workflow directory.
@@ -257,13 +959,81 @@ enum Command {
         /// Delete original symlink and recreate the symlink.
         #[clap(long)]
         force: bool,
+
+        /// Open the workflow in Alfred Preferences afterwards.
+        #[clap(long)]
+        open: bool,
+
+        /// Tell Alfred to reload the workflow afterwards.
+        #[clap(long)]
+        reload: bool,
+    },
+
+    /// Build and run the workflow with a query, printing its output.
+    Run {
+        /// The query to pass to the workflow.
+        query: Option<String>,
+
+        /// Package to run.
+        #[clap(long, short, value_name = "SPEC")]
+        package: Option<String>,
+
+        /// Run the specified binary.
+        #[clap(long, value_name = "NAME")]
+        bin: Option<String>,
+
+        /// Print each item as compact JSON on its own line, useful for
+        /// piping to `grep` or `jq`.
+        #[clap(long)]
+        ndjson: bool,
+    },
+
+    /// Print the workflow's log file from its cache directory.
+    Logs {
+        /// Package to show logs for.
+        #[clap(long, short, value_name = "SPEC")]
+        package: Option<String>,
+
+        /// The log file name in the cache directory, defaults to the only
+        /// `.log` file.
+        #[clap(long, value_name = "NAME")]
+        file: Option<PathBuf>,
+
+        /// Keep printing new lines as they are written.
+        #[clap(long, short, conflicts_with = "clear")]
+        follow: bool,
+
+        /// Truncate the log file.
+        #[clap(long)]
+        clear: bool,
+    },
+
+    /// Get or set workflow variables in `info.plist`.
+    Config {
+        /// Package to configure.
+        #[clap(long, short, value_name = "SPEC")]
+        package: Option<String>,
+
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Tell Alfred to reload the workflow.
+    Reload {
+        /// Package to reload.
+        #[clap(long, short, value_name = "SPEC")]
+        package: Option<String>,
     },
 
     /// Package the workflow as an `.alfredworkflow` file.
     Package {
-        /// Package to build.
+        /// Package to build, can be given multiple times.
         #[clap(long, short, value_name = "SPEC")]
-        package: Option<String>,
+        package: Vec<String>,
+
+        /// Continue with the remaining packages if one fails.
+        #[clap(long)]
+        keep_going: bool,
 
         /// Package only the specified binary.
         #[clap(long, value_name = "NAME")]
@@ -272,9 +1042,49 @@ enum Command {
         /// Build for the target triple.
         #[clap(long, value_name = "TRIPLE")]
         target: Option<String>,
+
+        /// Sign the binaries with this identity using `codesign`.
+        #[clap(long, value_name = "IDENTITY")]
+        sign: Option<String>,
+
+        /// Notarize the signed binaries using this `notarytool` keychain
+        /// profile.
+        #[clap(long, value_name = "PROFILE", requires = "sign")]
+        notarize: Option<String>,
+
+        /// The compression level to use for the workflow archive, from 0
+        /// (none) to 9 (best).
+        #[clap(
+            long,
+            value_name = "LEVEL",
+            default_value_t = 9,
+            value_parser = clap::value_parser!(u32).range(0..=9),
+        )]
+        compression: u32,
+
+        /// The modification time to record for the packaged files, use
+        /// `fixed` for reproducible packages.
+        #[clap(long, value_enum, value_name = "WHEN", default_value_t = alfred::Timestamp::Now)]
+        timestamp: alfred::Timestamp,
+
+        /// Extra arguments to pass to `cargo build`, e.g. `-- --offline`.
+        #[clap(last = true, value_name = "ARGS")]
+        args: Vec<OsString>,
     },
 }
 
+#[derive(Debug, Parser)]
+enum ConfigAction {
+    /// Set a workflow variable.
+    Set { key: String, value: String },
+
+    /// Print the value of a workflow variable.
+    Get { key: String },
+
+    /// Print all workflow variables as `KEY=VALUE` lines.
+    List,
+}
+
 #[derive(Debug, Parser)]
 #[clap(
     about,
@@ -285,39 +1095,168 @@ enum Command {
     subcommand_required(true),
 )]
 struct Opt {
+    /// Print what would be done without changing anything.
+    ///
+    /// Supported by `build`, `link`, `package`, and `reload`.
+    #[clap(long, global = true)]
+    dry_run: bool,
+
     #[clap(subcommand)]
     command: Command,
 }
 
+/// Returns the command line arguments, handling being run as a Cargo
+/// subcommand.
+///
+/// When run as `cargo powerpack <args>` Cargo invokes the `cargo-powerpack`
+/// binary with the arguments `powerpack <args>`, so the extra argument needs
+/// to be removed.
+fn args() -> Vec<OsString> {
+    let mut args: Vec<_> = env::args_os().collect();
+    let is_cargo_subcommand = args
+        .first()
+        .and_then(|arg0| Path::new(arg0).file_stem())
+        .is_some_and(|stem| stem == "cargo-powerpack");
+    if is_cargo_subcommand && args.get(1).is_some_and(|arg| arg == "powerpack") {
+        args.remove(1);
+    }
+    args
+}
+
 fn main() -> anyhow::Result<()> {
-    let Opt { command } = Opt::parse();
+    let Opt { dry_run, command } = Opt::parse_from(args());
+    if dry_run {
+        match command {
+            Command::Build { .. }
+            | Command::Link { .. }
+            | Command::Package { .. }
+            | Command::Reload { .. } => DRY_RUN.store(true, Ordering::Relaxed),
+            _ => bail!("`--dry-run` is only supported by `build`, `link`, `package`, and `reload`"),
+        }
+    }
+    if let Command::Run { .. } | Command::Build { json: true, .. } = command {
+        QUIET.store(true, Ordering::Relaxed);
+    }
     match command {
-        Command::New { path, name } => {
+        Command::New {
+            path,
+            name,
+            bundle_id,
+            argument,
+            icon,
+            open,
+        } => {
             fs::create_dir_all(&path)?;
-            init(&path, name)?;
+            init(&path, name, bundle_id, argument, icon.as_deref())?;
+            if open {
+                alfred::open_preferences(None)?;
+            }
         }
-        Command::Init { path, name } => {
+        Command::Init {
+            path,
+            name,
+            bundle_id,
+            argument,
+            icon,
+            open,
+        } => {
             let path = path.as_deref().unwrap_or_else(|| Path::new("."));
-            init(path, name)?;
+            init(path, name, bundle_id, argument, icon.as_deref())?;
+            if open {
+                alfred::open_preferences(None)?;
+            }
         }
         Command::Build {
             package,
+            keep_going,
             bin,
             release,
             target,
+            reload: should_reload,
+            post_build,
+            json,
+            args,
+        } => {
+            for_each_package(&package, keep_going, |package| {
+                build(
+                    package,
+                    bin.clone(),
+                    release,
+                    target.as_deref(),
+                    &args,
+                    post_build.as_deref(),
+                    json,
+                )?;
+                if should_reload {
+                    reload(package)?;
+                }
+                Ok(())
+            })?;
+        }
+        Command::Link {
+            package,
+            force,
+            open,
+            reload: should_reload,
         } => {
-            build(package.as_deref(), bin, release, target.as_deref())?;
+            link(package.as_deref(), force, open)?;
+            if should_reload {
+                reload(package.as_deref())?;
+            }
+        }
+        Command::Run {
+            query,
+            package,
+            bin,
+            ndjson,
+        } => {
+            run(package.as_deref(), bin, query, ndjson)?;
+        }
+        Command::Logs {
+            package,
+            file,
+            follow,
+            clear,
+        } => {
+            logs(package.as_deref(), file.as_deref(), follow, clear)?;
+        }
+        Command::Config { package, action } => {
+            config(package.as_deref(), action)?;
         }
-        Command::Link { package, force } => {
-            link(package.as_deref(), force)?;
+        Command::Reload { package } => {
+            reload(package.as_deref())?;
         }
         Command::Package {
             package,
+            keep_going,
             bin,
             target,
+            sign,
+            notarize,
+            compression,
+            timestamp,
+            args,
         } => {
-            build(package.as_deref(), bin, true, target.as_deref())?;
-            build_package(package.as_deref())?;
+            for_each_package(&package, keep_going, |package| {
+                build(
+                    package,
+                    bin.clone(),
+                    true,
+                    target.as_deref(),
+                    &args,
+                    None,
+                    false,
+                )?;
+                build_package(
+                    package,
+                    &bin,
+                    sign.as_deref(),
+                    notarize.as_deref(),
+                    compression,
+                    timestamp,
+                    &args,
+                )
+            })?;
         }
     }
     Ok(())
@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use anyhow::{bail, Context, Result};
+
+/// Run a command, failing with its stderr if it did not exit successfully.
+fn run(cmd: &mut process::Command) -> Result<Vec<u8>> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let output = cmd
+        .output()
+        .with_context(|| format!("failed to run `{program}`"))?;
+    if !output.status.success() {
+        bail!(
+            "`{program}` did not exit successfully\n\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Sign a binary with the given identity using `codesign`.
+///
+/// The hardened runtime and a secure timestamp are enabled because both are
+/// required for notarization.
+pub fn codesign(path: &Path, identity: &str) -> Result<()> {
+    run(process::Command::new("codesign")
+        .args(["--force", "--options", "runtime", "--timestamp", "--sign"])
+        .arg(identity)
+        .arg(path))?;
+    Ok(())
+}
+
+/// Submit a signed binary for notarization using `notarytool` and wait for
+/// the result.
+///
+/// The credentials are read from the given keychain profile, which can be
+/// created using `xcrun notarytool store-credentials`.
+pub fn notarize(path: &Path, tmp_dir: &Path, profile: &str) -> Result<()> {
+    // notarytool only accepts archives so zip the binary up first
+    let file_name = path.file_name().context("expected file name")?;
+    let zip = tmp_dir.join(file_name).with_extension("zip");
+    run(process::Command::new("ditto")
+        .args(["-c", "-k", "--keepParent"])
+        .arg(path)
+        .arg(&zip))?;
+
+    let result = run(process::Command::new("xcrun")
+        .args(["notarytool", "submit", "--wait", "--output-format", "json"])
+        .arg("--keychain-profile")
+        .arg(profile)
+        .arg(&zip));
+    fs::remove_file(&zip)?;
+
+    let value: serde_json::Value = serde_json::from_slice(&result?)?;
+    match value["status"].as_str() {
+        Some("Accepted") => Ok(()),
+        status => bail!(
+            "notarization of `{}` failed with status {}\n\n\
+             hint: run `xcrun notarytool log {}` for details",
+            path.display(),
+            status.unwrap_or("unknown"),
+            value["id"].as_str().unwrap_or("<id>"),
+        ),
+    }
+}
@@ -1,3 +1,8 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+
 #[test]
 fn main_template() {
     #[allow(dead_code)]
@@ -5,3 +10,252 @@ fn main_template() {
         include!("../src/main.template.rs");
     }
 }
+
+/// Creates a small workflow package with a post build command that writes to
+/// stdout, returning its directory.
+fn workflow_package() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("workflow-package");
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                r#"[package]
+name = "test-workflow"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.powerpack]
+post_build = "echo post build"
+
+[dependencies]
+powerpack = {{ path = "{}" }}
+
+[workspace]
+"#,
+                root.display()
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("src/main.rs"),
+            r#"fn main() {
+    let query = powerpack::env::query().unwrap_or_default();
+    let items = ["a", "b"].map(|t| powerpack::Item::new(t).subtitle(&query));
+    powerpack::output(items).unwrap();
+}
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("workflow")).unwrap();
+        fs::write(
+            dir.join("workflow/info.plist"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>bundleid</key>
+	<string>com.example.test-workflow</string>
+	<key>name</key>
+	<string>test-workflow</string>
+	<key>objects</key>
+	<array/>
+</dict>
+</plist>
+"#,
+        )
+        .unwrap();
+        // Reuse the locked dependency versions of this workspace
+        let _ = fs::copy(root.join("Cargo.lock"), dir.join("Cargo.lock"));
+        dir
+    })
+}
+
+/// Runs the `powerpack` binary in the given directory.
+fn powerpack(dir: &Path, args: &[&str]) -> process::Output {
+    let output = process::Command::new(env!("CARGO_BIN_EXE_powerpack"))
+        .args(args)
+        .current_dir(dir)
+        .env("CARGO_TARGET_DIR", dir.join("target"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "`powerpack {}` failed\n\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[test]
+fn run_stdout_is_json() {
+    let output = powerpack(workflow_package(), &["run", "hello"]);
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["items"][0]["title"], "a");
+    assert_eq!(value["items"][1]["subtitle"], "hello");
+}
+
+#[test]
+fn run_ndjson() {
+    let output = powerpack(workflow_package(), &["run", "--ndjson", "hello"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let titles: Vec<_> = stdout
+        .lines()
+        .map(|line| {
+            let item: serde_json::Value = serde_json::from_str(line).unwrap();
+            item["title"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(titles, ["a", "b"]);
+}
+
+#[test]
+fn build_json_dry_run() {
+    let output = powerpack(workflow_package(), &["build", "--json", "--dry-run"]);
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["package"], "test-workflow");
+}
+
+#[test]
+fn package_forwards_cargo_args() {
+    let dir = workflow_package();
+    powerpack(dir, &["package", "--", "--offline"]);
+    assert!(dir
+        .join("target/workflow/test-workflow.alfredworkflow")
+        .exists());
+}
+
+#[allow(dead_code)]
+mod alfred {
+    include!("../src/alfred.rs");
+}
+
+#[test]
+fn stale_links_ignores_other_workflows() {
+    use std::os::unix::fs::symlink;
+
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR")).join("stale-links");
+    let _ = fs::remove_dir_all(&tmp);
+    let workflows_dir = tmp.join("workflows");
+    let manifest_dir = tmp.join("new/my-workflow");
+    let workflow_dir = manifest_dir.join("workflow");
+    fs::create_dir_all(&workflows_dir).unwrap();
+    fs::create_dir_all(&workflow_dir).unwrap();
+    fs::create_dir_all(tmp.join("other")).unwrap();
+
+    let moved = tmp.join("old/my-workflow/workflow");
+    symlink(&moved, workflows_dir.join("a")).unwrap();
+    symlink(
+        tmp.join("old/other-workflow/workflow"),
+        workflows_dir.join("b"),
+    )
+    .unwrap();
+    symlink(tmp.join("old/unrelated"), workflows_dir.join("c")).unwrap();
+    symlink(tmp.join("other"), workflows_dir.join("d")).unwrap();
+    assert_eq!(
+        alfred::stale_links(&workflows_dir, &manifest_dir, &workflow_dir),
+        [moved]
+    );
+
+    fs::remove_file(workflows_dir.join("a")).unwrap();
+    assert!(alfred::stale_links(&workflows_dir, &manifest_dir, &workflow_dir).is_empty());
+}
+
+/// Returns the `(xpos, ypos)` of each object in the graph in the order they
+/// were added.
+fn graph_positions(graph: &alfred::Graph) -> Vec<(i64, i64)> {
+    let uidata = graph.uidata();
+    graph
+        .objects()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|obj| {
+            let uid = obj.as_dictionary().unwrap()["uid"].as_string().unwrap();
+            let pos = uidata.as_dictionary().unwrap()[uid]
+                .as_dictionary()
+                .unwrap();
+            (
+                pos["xpos"].as_signed_integer().unwrap(),
+                pos["ypos"].as_signed_integer().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn graph_object(graph: &mut alfred::Graph) -> alfred::Uid {
+    graph.add_object("alfred.workflow.test", plist::Dictionary::new().into())
+}
+
+#[test]
+fn graph_linear() {
+    let mut graph = alfred::Graph::new();
+    let a = graph_object(&mut graph);
+    let b = graph_object(&mut graph);
+    let c = graph_object(&mut graph);
+    graph.connect(&b, &c);
+    graph.connect(&a, &b);
+    assert_eq!(graph_positions(&graph), [(50, 50), (225, 50), (400, 50)]);
+
+    let connections = graph.connections();
+    let connections = connections.as_dictionary().unwrap();
+    assert_eq!(connections.len(), 2);
+}
+
+#[test]
+fn graph_branching() {
+    let mut graph = alfred::Graph::new();
+    let a = graph_object(&mut graph);
+    let b = graph_object(&mut graph);
+    let c = graph_object(&mut graph);
+    let d = graph_object(&mut graph);
+    graph.connect(&a, &b);
+    graph.connect(&a, &c);
+    graph.connect(&b, &d);
+    graph.connect(&c, &d);
+    assert_eq!(
+        graph_positions(&graph),
+        [(50, 50), (225, 50), (225, 170), (400, 50)]
+    );
+
+    let connections = graph.connections();
+    let connections = connections.as_dictionary().unwrap();
+    assert_eq!(connections.len(), 3);
+    let uid = graph.objects().as_array().unwrap()[0]
+        .as_dictionary()
+        .unwrap()["uid"]
+        .as_string()
+        .unwrap()
+        .to_owned();
+    assert_eq!(connections[&uid].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn graph_cyclic() {
+    let mut graph = alfred::Graph::new();
+    let a = graph_object(&mut graph);
+    let b = graph_object(&mut graph);
+    let c = graph_object(&mut graph);
+    graph.connect(&a, &b);
+    graph.connect(&b, &c);
+    graph.connect(&c, &b);
+    graph.connect(&a, &a);
+    assert_eq!(graph_positions(&graph), [(400, 50), (400, 170), (400, 290)]);
+}
+
+#[test]
+fn graph_disabled_object() {
+    let mut graph = alfred::Graph::new();
+    graph_object(&mut graph);
+    graph.add_object_disabled("alfred.workflow.test", plist::Dictionary::new().into());
+    let objects = graph.objects();
+    let objects = objects.as_array().unwrap();
+    assert_eq!(objects[0].as_dictionary().unwrap().get("disabled"), None);
+    assert_eq!(
+        objects[1].as_dictionary().unwrap()["disabled"],
+        plist::Value::Boolean(true)
+    );
+}
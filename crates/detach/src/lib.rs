@@ -19,6 +19,31 @@
 //! only one child process is running at a time by first acquiring a file mutex
 //! in the spawned function.
 //!
+//! ### ⚠️ Threads
+//!
+//! Only the thread that calls [`spawn`] is copied into the child process. If
+//! other threads exist, for example an async runtime or a background logger,
+//! then any locks they held at the time of the fork stay locked forever in
+//! the child and using them will deadlock. Call [`spawn`] before starting any
+//! threads, or use [`spawn_checked`] which returns an error instead of forking
+//! a multi-threaded process.
+//!
+//! ### ⚠️ Buffered output
+//!
+//! Anything buffered in memory is copied into the child process too, and
+//! would be written twice if both processes flush it. The standard
+//! stdout/stderr handles and the global [`log`] logger are flushed before
+//! forking, but if you wrap stdout in your own buffer, such as an
+//! [`io::BufWriter`], you must flush it before calling [`spawn`].
+//!
+//! ### 🧪 Testing
+//!
+//! If the `POWERPACK_NO_DETACH` environment variable is set to `1` then no
+//! child process is forked. Instead the given function is run to completion in
+//! the current process and any error it returns is returned from [`spawn`].
+//! This makes code that uses this crate deterministic under `cargo test` and
+//! in CI.
+//!
 //! # Examples
 //!
 //! ```no-compile
@@ -31,9 +56,14 @@
 //! }).expect("forked child process");
 //! ```
 
+use std::env;
+use std::ffi::OsString;
+use std::fs;
 use std::io;
+use std::io::Write;
 use std::panic;
-use std::panic::PanicInfo;
+use std::panic::PanicHookInfo;
+use std::path::Path;
 use std::process;
 
 #[derive(Debug, Clone, Copy)]
@@ -44,6 +74,12 @@ enum Fork {
 
 /// Fork the current process.
 fn fork() -> io::Result<Fork> {
+    // Flush any buffered output, otherwise both processes would write it.
+    // This is best-effort, e.g. stdout might be a closed pipe, which should
+    // not prevent forking.
+    log::logger().flush();
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
     // SAFETY: We are handling the error correctly.
     let r = unsafe { libc::fork() };
     handle_err(r).map(|r| match r {
@@ -61,6 +97,45 @@ fn close_std_fds() -> io::Result<()> {
     Ok(())
 }
 
+/// Returns the number of threads in the current process.
+#[cfg(target_os = "macos")]
+fn thread_count() -> io::Result<usize> {
+    use std::mem;
+
+    let size = mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    // SAFETY: `proc_taskinfo` is a plain C struct so all zeroes is valid.
+    let mut info: libc::proc_taskinfo = unsafe { mem::zeroed() };
+    // SAFETY: The buffer is valid for `size` bytes and we check the result.
+    let r = unsafe {
+        libc::proc_pidinfo(
+            libc::getpid(),
+            libc::PROC_PIDTASKINFO,
+            0,
+            &mut info as *mut libc::proc_taskinfo as *mut libc::c_void,
+            size,
+        )
+    };
+    if r != size {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(info.pti_threadnum as usize)
+}
+
+/// Returns the number of threads in the current process.
+#[cfg(target_os = "linux")]
+fn thread_count() -> io::Result<usize> {
+    Ok(fs::read_dir("/proc/self/task")?.count())
+}
+
+/// Returns the number of threads in the current process.
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn thread_count() -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "counting threads is not supported on this platform",
+    ))
+}
+
 fn handle_err(res: i32) -> io::Result<i32> {
     match res {
         -1 => Err(io::Error::last_os_error()),
@@ -68,7 +143,7 @@ fn handle_err(res: i32) -> io::Result<i32> {
     }
 }
 
-fn panic_hook(info: &PanicInfo<'_>) {
+fn panic_hook(info: &PanicHookInfo<'_>) {
     let msg = match info.payload().downcast_ref::<&'static str>() {
         Some(s) => *s,
         None => match info.payload().downcast_ref::<String>() {
@@ -86,6 +161,77 @@ pub fn spawn<F>(f: F) -> io::Result<()>
 where
     F: FnOnce(),
 {
+    spawn_child(|| {
+        f();
+        Ok(())
+    })
+}
+
+/// Execute a function in a child process, unless the current process has
+/// more than one thread.
+///
+/// Forking a multi-threaded process is dangerous, see the [crate] level
+/// documentation for more. This function checks the number of threads first
+/// and returns an error instead of forking if there are any other threads.
+pub fn spawn_checked<F>(f: F) -> io::Result<()>
+where
+    F: FnOnce(),
+{
+    let n = if no_detach() { 1 } else { thread_count()? };
+    if n > 1 {
+        return Err(io::Error::other(format!(
+            "refusing to fork a process with {n} threads"
+        )));
+    }
+    spawn(f)
+}
+
+/// Execute a function in a child process and write the result to a file.
+///
+/// If the function succeeds the returned bytes are written to a temporary
+/// file which is then renamed to `path`, so readers never observe a partially
+/// written file. If the function fails the error is logged and `path` is left
+/// untouched.
+///
+/// See the [crate] level documentation for more.
+///
+/// # Examples
+///
+/// ```no_run
+/// powerpack_detach::spawn_writing("/path/to/cache.json", || {
+///
+///     // some expensive operation that shouldn't block Alfred
+///     //
+///     // e.g. fetch a remote resource
+///
+///     Ok(b"{}".to_vec())
+/// }).expect("forked child process");
+/// ```
+pub fn spawn_writing<P, F>(path: P, f: F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> io::Result<Vec<u8>>,
+{
+    let path = path.as_ref();
+    spawn_child(|| {
+        let bytes = f()?;
+        write_atomic(path, &bytes)
+    })
+}
+
+/// Whether to run functions inline instead of forking, see the [crate] level
+/// documentation.
+fn no_detach() -> bool {
+    env::var_os("POWERPACK_NO_DETACH").is_some_and(|v| v == "1")
+}
+
+fn spawn_child<F>(f: F) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<()>,
+{
+    if no_detach() {
+        return f();
+    }
     match fork()? {
         Fork::Parent => Ok(()),
         Fork::Child => match exec_child(f) {
@@ -102,10 +248,19 @@ where
 
 fn exec_child<F>(f: F) -> io::Result<()>
 where
-    F: FnOnce(),
+    F: FnOnce() -> io::Result<()>,
 {
     close_std_fds()?;
     panic::set_hook(Box::new(panic_hook));
-    f();
-    Ok(())
+    f()
+}
+
+/// Write a file by writing to a temporary file and renaming it.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file_name = OsString::from(".");
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(format!(".{}.tmp", process::id()));
+    let tmp = path.with_file_name(file_name);
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
 }
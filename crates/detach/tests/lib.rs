@@ -0,0 +1,53 @@
+use std::cell::Cell;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Run the given functions inline instead of forking.
+fn no_detach() {
+    env::set_var("POWERPACK_NO_DETACH", "1");
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("powerpack-detach-test-{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn spawn_no_detach() {
+    no_detach();
+    let ran = Cell::new(false);
+    powerpack_detach::spawn(|| ran.set(true)).unwrap();
+    assert!(ran.get());
+}
+
+#[test]
+fn spawn_checked_no_detach() {
+    no_detach();
+    // The test harness runs tests on other threads, but nothing is forked
+    let ran = Cell::new(false);
+    powerpack_detach::spawn_checked(|| ran.set(true)).unwrap();
+    assert!(ran.get());
+}
+
+#[test]
+fn spawn_writing_no_detach() {
+    no_detach();
+    let dir = temp_dir("spawn-writing");
+    let path = dir.join("cache.json");
+    fs::write(&path, "old").unwrap();
+
+    powerpack_detach::spawn_writing(&path, || Ok(b"new".to_vec())).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    // The temporary file was renamed into place
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    let err = powerpack_detach::spawn_writing(&path, || Err(io::Error::other("oops")));
+    assert_eq!(err.unwrap_err().to_string(), "oops");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
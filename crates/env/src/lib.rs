@@ -4,6 +4,8 @@
 
 use std::env;
 use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
 use std::path::PathBuf;
 
 /// Fetches the environment variable `key` from the current process.
@@ -21,6 +23,26 @@ pub fn var<K: AsRef<OsStr>>(key: K) -> Option<String> {
     env::var(key).ok().filter(|s| !s.is_empty())
 }
 
+/// Fetches the environment variable `key` from the current process, without
+/// panicking on an invalid key.
+///
+/// This function is the same as [`var`] except that it first checks the key,
+/// which makes it suitable for keys that come from untrusted input, e.g. user
+/// configuration.
+///
+/// # None
+///
+/// Returns `None` in the same cases as [`var`] and also if the key is empty
+/// or contains an ASCII equals sign `=` or the NUL character `\0`.
+pub fn try_var<K: AsRef<OsStr>>(key: K) -> Option<String> {
+    let key = key.as_ref();
+    let bytes = key.as_encoded_bytes();
+    if bytes.is_empty() || bytes.contains(&b'=') || bytes.contains(&b'\0') {
+        return None;
+    }
+    var(key)
+}
+
 /// Fetches the environment variable `key` from the current process.
 ///
 /// This function is similar to [`std::env::var_os(key).ok()`][std::env::var]
@@ -39,6 +61,68 @@ pub fn var_os<K: AsRef<OsStr>>(key: K) -> Option<OsString> {
     env::var_os(key).filter(|s| !s.is_empty())
 }
 
+/// The query passed to the workflow by Alfred.
+///
+/// This is the first argument passed to the process, skipping the binary name.
+/// Returns `None` if there is no argument, or if the argument is empty or
+/// contains only whitespace.
+///
+/// If the workflow is invoked such that the query is not the first argument,
+/// for example when a launcher script prepends a subcommand, then the
+/// `POWERPACK_QUERY_INDEX` environment variable can be set to the index of the
+/// query argument. This is the same index as [`query_at`] accepts.
+///
+/// Whether Alfred passes an argument at all depends on the script filter
+/// settings in the workflow's `info.plist`:
+/// - `argumenttype`: if the argument is optional (`1`) the script filter runs
+///   as soon as the keyword is typed, possibly without a query. If no argument
+///   is accepted (`2`) this will always return `None`.
+/// - `withspace`: if set, the user must type a space after the keyword before
+///   the query. The space is not included in the query.
+/// - `argumenttreatemptyqueryasnil`: if set, Alfred does not pass an argument
+///   when the query is empty, otherwise it passes an empty string. This
+///   function treats both cases the same.
+///
+/// If the argument is not valid UTF-8 then any invalid sequences are replaced
+/// with `U+FFFD REPLACEMENT CHARACTER`, use [`query_os`] to get the argument
+/// unchanged.
+pub fn query() -> Option<String> {
+    query_os().map(|s| s.to_string_lossy().into_owned())
+}
+
+/// The query passed to the workflow by Alfred as an OS string.
+///
+/// Same as [`query`] but does not require the argument to be valid UTF-8.
+pub fn query_os() -> Option<OsString> {
+    let index = var("POWERPACK_QUERY_INDEX")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1);
+    query_os_at(index)
+}
+
+/// The argument at `index` passed to the workflow.
+///
+/// Index `0` is the binary name, so `query_at(1)` is the same as [`query`]
+/// without the `POWERPACK_QUERY_INDEX` override. Returns `None` if there is
+/// no argument at the index, or if the argument is empty or contains only
+/// whitespace.
+///
+/// If the argument is not valid UTF-8 then any invalid sequences are replaced
+/// with `U+FFFD REPLACEMENT CHARACTER`, use [`query_os_at`] to get the
+/// argument unchanged.
+pub fn query_at(index: usize) -> Option<String> {
+    query_os_at(index).map(|s| s.to_string_lossy().into_owned())
+}
+
+/// The argument at `index` passed to the workflow as an OS string.
+///
+/// Same as [`query_at`] but does not require the argument to be valid UTF-8.
+pub fn query_os_at(index: usize) -> Option<OsString> {
+    env::args_os()
+        .nth(index)
+        .filter(|s| !s.to_string_lossy().trim().is_empty())
+}
+
 /// Whether or not the user currently has the Alfred debug panel open.
 pub fn is_debug() -> bool {
     var("alfred_debug").as_deref() == Some("1")
@@ -66,6 +150,35 @@ pub fn version_build() -> Option<u32> {
     var("alfred_version_build").and_then(|s| s.parse().ok())
 }
 
+/// The current Alfred theme's background color as `(red, green, blue, alpha)`.
+///
+/// Alfred sets this in the format `rgba(r,g,b,a)`, e.g. `rgba(255,255,255,0.98)`.
+/// Returns `None` if the variable is not set or cannot be parsed.
+pub fn theme_background() -> Option<(u8, u8, u8, f32)> {
+    let s = var("alfred_theme_background")?;
+    let inner = s.trim().strip_prefix("rgba(")?.strip_suffix(')')?;
+    let mut parts = inner.split(',').map(|p| p.trim().parse::<f32>().ok());
+    let mut channel = || {
+        parts
+            .next()
+            .flatten()
+            .map(|c| c.clamp(0.0, 255.0).round() as u8)
+    };
+    let (r, g, b) = (channel()?, channel()?, channel()?);
+    let a = parts.next().flatten()?;
+    parts.next().is_none().then_some((r, g, b, a))
+}
+
+/// Whether the current Alfred theme has a dark background.
+///
+/// This is based on the relative luminance of [`theme_background`]. Returns
+/// `None` if the theme background cannot be determined.
+pub fn is_dark_theme() -> Option<bool> {
+    let (r, g, b, _) = theme_background()?;
+    let luminance = 0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b);
+    Some(luminance < 128.0)
+}
+
 /// The bundle ID of the currently running workflow.
 pub fn workflow_bundle_id() -> Option<String> {
     var("alfred_workflow_bundleid")
@@ -99,3 +212,36 @@ pub fn workflow_cache() -> Option<PathBuf> {
 pub fn workflow_data() -> Option<PathBuf> {
     var_os("alfred_workflow_data").map(PathBuf::from)
 }
+
+/// The recommended directory for volatile workflow data, creating it if it
+/// does not exist.
+///
+/// # Errors
+///
+/// Returns an error if the `alfred_workflow_cache` environment variable is not
+/// set or if the directory could not be created.
+pub fn workflow_cache_dir() -> io::Result<PathBuf> {
+    create_dir("alfred_workflow_cache", workflow_cache())
+}
+
+/// The recommended directory for non-volatile workflow data, creating it if
+/// it does not exist.
+///
+/// # Errors
+///
+/// Returns an error if the `alfred_workflow_data` environment variable is not
+/// set or if the directory could not be created.
+pub fn workflow_data_dir() -> io::Result<PathBuf> {
+    create_dir("alfred_workflow_data", workflow_data())
+}
+
+fn create_dir(key: &str, dir: Option<PathBuf>) -> io::Result<PathBuf> {
+    let dir = dir.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("environment variable `{key}` is not set"),
+        )
+    })?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+
+/// Run the `query_child` test in a child process with the given arguments
+/// after the test filter, asserting that `query()` returns `expected`.
+fn assert_query(index: Option<&str>, args: &[&str], expected: Option<&str>) {
+    let mut cmd = process::Command::new(env::current_exe().unwrap());
+    cmd.args(["query_child", "--exact", "--quiet"])
+        .args(args)
+        .env("POWERPACK_TEST_EXPECTED_QUERY", format!("{expected:?}"))
+        .env_remove("POWERPACK_QUERY_INDEX");
+    if let Some(index) = index {
+        cmd.env("POWERPACK_QUERY_INDEX", index);
+    }
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("1 passed"), "{stdout}");
+}
+
+#[test]
+fn query_child() {
+    let Ok(expected) = env::var("POWERPACK_TEST_EXPECTED_QUERY") else {
+        return;
+    };
+    assert_eq!(format!("{:?}", powerpack_env::query()), expected);
+}
+
+#[test]
+fn query() {
+    // The first argument is the test filter
+    assert_query(None, &[], Some("query_child"));
+    assert_query(Some("4"), &["hello"], Some("hello"));
+    assert_query(Some("4"), &[" hello "], Some(" hello "));
+    assert_query(Some("4"), &[""], None);
+    assert_query(Some("4"), &["  \t"], None);
+    assert_query(Some("5"), &["hello"], None);
+    assert_query(Some("invalid"), &["hello"], Some("query_child"));
+}
+
+#[test]
+fn query_at() {
+    assert_eq!(powerpack_env::query_at(1000), None);
+    let arg0 = env::args().next().unwrap();
+    assert_eq!(powerpack_env::query_at(0), Some(arg0));
+}
+
+#[test]
+fn workflow_cache_dir() {
+    env::remove_var("alfred_workflow_cache");
+    let err = powerpack_env::workflow_cache_dir().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    let dir = env::temp_dir().join("powerpack-env-test-cache/nested");
+    let _ = fs::remove_dir_all(dir.parent().unwrap());
+    env::set_var("alfred_workflow_cache", &dir);
+    assert_eq!(powerpack_env::workflow_cache_dir().unwrap(), dir);
+    assert!(dir.is_dir());
+    env::remove_var("alfred_workflow_cache");
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn workflow_data_dir() {
+    env::set_var("alfred_workflow_data", "");
+    let err = powerpack_env::workflow_data_dir().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    let dir = env::temp_dir().join("powerpack-env-test-data/nested");
+    let _ = fs::remove_dir_all(dir.parent().unwrap());
+    env::set_var("alfred_workflow_data", &dir);
+    assert_eq!(powerpack_env::workflow_data_dir().unwrap(), dir);
+    assert!(dir.is_dir());
+    env::remove_var("alfred_workflow_data");
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
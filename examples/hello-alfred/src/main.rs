@@ -3,7 +3,8 @@ use std::error::Error;
 use std::io;
 use std::time::Duration;
 
-use powerpack::*;
+use powerpack::prelude::*;
+use powerpack::value;
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Alfred passes in a single argument for the user query.
@@ -30,13 +30,22 @@
 //! # }
 //! ```
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+#[cfg(feature = "env")]
+use std::fs;
 use std::io;
 use std::path::PathBuf;
+#[cfg(feature = "env")]
+use std::process;
+use std::str::FromStr;
 use std::time::Duration;
 
+use serde::de::Error as _;
 use serde::ser::SerializeStruct;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub use serde_json::json as value;
 pub use serde_json::Value;
@@ -47,6 +56,30 @@ pub use powerpack_detach as detach;
 #[cfg(feature = "env")]
 pub use powerpack_env as env;
 
+pub mod query;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "update")]
+pub mod update;
+
+/// The types and functions most workflows need.
+///
+/// This is the recommended way to import from this crate, unlike a glob
+/// import of the crate root it will not bring less common names into scope.
+///
+/// # Examples
+///
+/// ```
+/// use powerpack::prelude::*;
+///
+/// let item = Item::new("Hello world!").icon(Icon::with_type("public.folder"));
+/// ```
+pub mod prelude {
+    pub use crate::{
+        error_item, output, run, try_output, Icon, Item, Items, Key, Kind, Modifier, Output,
+    };
+}
+
 fn is_default<T: Default + PartialEq>(t: &T) -> bool {
     t == &T::default()
 }
@@ -55,8 +88,14 @@ fn is_default<T: Default + PartialEq>(t: &T) -> bool {
 // Definitions
 ////////////////////////////////////////////////////////////////////////////////
 
+/// An error returned when a value is not valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
 /// An arg, either a string or a sequence of strings.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(untagged)]
 enum Arg {
     /// A single string.
@@ -66,7 +105,7 @@ enum Arg {
 }
 
 /// A keyboard modifier key.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Key {
     /// ⌘
     #[serde(rename = "cmd")]
@@ -86,7 +125,10 @@ pub enum Key {
 }
 
 /// A keyboard modifier combination.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+///
+/// Combinations are ordered so that modifiers are always serialized in the
+/// same order, single keys first in the order they are declared in [`Key`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[serde(untagged)]
 enum Keys {
     One(Key),
@@ -111,8 +153,9 @@ enum IconInner {
 pub struct Icon(IconInner);
 
 /// The type of item.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Kind {
+    #[default]
     #[serde(rename = "default")]
     Default,
     #[serde(rename = "file")]
@@ -121,7 +164,7 @@ pub enum Kind {
     FileSkipCheck,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct Text {
     /// Defines the text the user will get when copying the item (⌘+C).
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -132,7 +175,7 @@ struct Text {
     large_type: Option<String>,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct Data {
     /// The subtitle displayed in the result row.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -149,6 +192,14 @@ struct Data {
     /// Mark whether the item is valid when the modifier is pressed.
     #[serde(skip_serializing_if = "Option::is_none")]
     valid: Option<bool>,
+
+    /// The type of item when the modifier is pressed.
+    #[serde(rename = "type", default, skip_serializing_if = "is_default")]
+    kind: Kind,
+
+    /// Defines the copied or large type text when the modifier is pressed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<Text>,
 }
 
 /// The modifier settings for an [`Item`] when a modifier key is pressed.
@@ -162,7 +213,7 @@ pub struct Modifier {
 }
 
 /// An Alfred script filter item.
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Item {
     /// The title displayed in the result row.
     title: String,
@@ -191,17 +242,21 @@ pub struct Item {
     #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
     matches: Option<String>,
 
+    /// Whether `matches` is derived from the title and subtitle.
+    #[serde(skip)]
+    match_title_and_subtitle: bool,
+
     /// Populates the search field when the user auto-completes the result.
     #[serde(skip_serializing_if = "Option::is_none")]
     autocomplete: Option<String>,
 
     /// The type of item.
-    #[serde(rename = "type", skip_serializing_if = "is_default")]
+    #[serde(rename = "type", default, skip_serializing_if = "is_default")]
     kind: Kind,
 
     /// Control how the modifier keys react.
-    #[serde(rename = "mods", skip_serializing_if = "HashMap::is_empty")]
-    modifiers: HashMap<Keys, Data>,
+    #[serde(rename = "mods", default, skip_serializing_if = "BTreeMap::is_empty")]
+    modifiers: BTreeMap<Keys, Data>,
 
     /// Defines the copied or large type text for this item.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -211,17 +266,70 @@ pub struct Item {
     #[serde(rename = "quicklookurl", skip_serializing_if = "Option::is_none")]
     quicklook_url: Option<String>,
 
-    #[serde(skip_serializing_if = "Value::is_null")]
+    #[serde(default, skip_serializing_if = "Value::is_null")]
     action: Value,
+
+    /// Variables which are passed out of the script filter if this item is
+    /// actioned.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, String>,
+}
+
+/// How Alfred caches script filter results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ResultCache {
+    /// The time to live of the cached results.
+    seconds: u64,
+
+    /// Whether to show the cached results while the script filter reruns.
+    #[serde(rename = "loosereload", default)]
+    loose_reload: bool,
+}
+
+/// A borrowed [`Item`] which does not allocate.
+///
+/// Creating an [`Item`] allocates a `String` for each field that is set,
+/// which adds up when generating many candidate items only for most of them
+/// to be filtered out. An `ItemRef` only borrows its fields, so creating one
+/// never allocates. Convert the items that survive filtering into owned
+/// items using [`Item::from`], which performs the same allocations as
+/// building the [`Item`] directly.
+///
+/// # Examples
+///
+/// ```
+/// use powerpack::{Item, ItemRef};
+///
+/// let names = ["alpha", "beta", "gamma"];
+/// let items: Vec<Item> = names
+///     .iter()
+///     .map(|name| ItemRef::new(name).arg(name))
+///     .filter(|item| item.title().contains('m'))
+///     .map(Item::from)
+///     .collect();
+///
+/// assert_eq!(items, [Item::new("gamma").arg("gamma")]);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ItemRef<'a> {
+    title: &'a str,
+    subtitle: Option<&'a str>,
+    uid: Option<&'a str>,
+    arg: Option<&'a str>,
+    valid: Option<bool>,
+    matches: Option<&'a str>,
+    autocomplete: Option<&'a str>,
 }
 
 /// The output of a workflow (i.e. input for the script filter)
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Output {
     /// The interval in seconds after which to rerun the script filter.
     #[serde(
+        default,
         skip_serializing_if = "Option::is_none",
-        serialize_with = "duration_as_secs"
+        serialize_with = "duration_as_secs",
+        deserialize_with = "duration_from_secs"
     )]
     rerun: Option<Duration>,
 
@@ -229,8 +337,77 @@ pub struct Output {
     #[serde(rename = "skipknowledge", skip_serializing_if = "Option::is_none")]
     skip_knowledge: Option<bool>,
 
+    /// Session variables which are passed back to the script filter on rerun
+    /// and out of the script filter when an item is actioned.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, String>,
+
+    /// Controls how Alfred caches the results of this output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<ResultCache>,
+
     /// Each row item.
+    #[serde(default)]
     items: Vec<Item>,
+
+    /// The item to write if there are no items.
+    #[serde(skip)]
+    placeholder: Option<Item>,
+}
+
+/// A collection of items with common post-processing helpers.
+///
+/// Workflows often need to sort, deduplicate, and limit the number of results
+/// before outputting them. This can be passed anywhere that accepts an
+/// iterator of items, e.g. [`Output::items`] or [`output`].
+///
+/// # Examples
+///
+/// ```
+/// use powerpack::{Item, Items};
+///
+/// let items: Items = ["b", "a", "b"]
+///     .into_iter()
+///     .map(|s| Item::new(s).uid(s))
+///     .collect();
+///
+/// let items = items.dedup_by_uid().truncate(1);
+/// assert_eq!(items.len(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Items(Vec<Item>);
+
+/// A helper for showing a loading item until a background job is done.
+///
+/// A common pattern is to start a slow job in the background, for example
+/// using [`detach`](https://docs.rs/powerpack-detach), show a loading item,
+/// and rerun the script filter until the results are ready. This helper
+/// tracks whether this is the first run using a session variable, and builds
+/// the appropriate output for each run.
+///
+/// # Examples
+///
+/// ```no_run
+/// use powerpack::{Item, Loading};
+///
+/// # fn start_job() {}
+/// # fn job_result() -> Option<Vec<Item>> { None }
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let loading = Loading::new("fetch_started");
+///
+/// if loading.is_first_run() {
+///     start_job();
+/// }
+///
+/// loading.output(job_result()).write(std::io::stdout())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loading {
+    key: String,
+    item: Item,
+    rerun: Duration,
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -257,6 +434,75 @@ where
     s.serialize_str(&out)
 }
 
+impl FromStr for Key {
+    type Err = Error;
+
+    /// Parse a key from Alfred's name for it, e.g. `cmd`, or a common alias,
+    /// e.g. `command`. Matching is case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cmd" | "command" | "⌘" => Ok(Self::Command),
+            "alt" | "opt" | "option" | "⌥" => Ok(Self::Option),
+            "ctrl" | "control" | "⌃" => Ok(Self::Control),
+            "shift" | "⇧" => Ok(Self::Shift),
+            "fn" | "function" => Ok(Self::Function),
+            _ => Err(Error::new(format!("unknown modifier key `{s}`"))),
+        }
+    }
+}
+
+impl TryFrom<&str> for Key {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::File => "file",
+            Self::FileSkipCheck => "file:skipcheck",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Kind {
+    type Err = Error;
+
+    /// Parse a kind from the same strings used in the script filter JSON.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "file" => Ok(Self::File),
+            "file:skipcheck" => Ok(Self::FileSkipCheck),
+            _ => Err(Error::new(format!("unknown item type `{s}`"))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Keys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let mut keys = s
+            .split('+')
+            .map(|key| key.parse().map_err(D::Error::custom))
+            .collect::<Result<Vec<_>, _>>()?;
+        match keys.len() {
+            1 => Ok(Keys::One(keys.remove(0))),
+            _ => Ok(Keys::Many(keys)),
+        }
+    }
+}
+
 impl Serialize for Icon {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match &self.0 {
@@ -281,6 +527,41 @@ impl Serialize for Icon {
     }
 }
 
+impl<'de> Deserialize<'de> for Icon {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw {
+            #[serde(rename = "type")]
+            kind: Option<String>,
+            path: String,
+        }
+
+        let Raw { kind, path } = Raw::deserialize(deserializer)?;
+        match kind.as_deref() {
+            None => Ok(Self(IconInner::Image(path.into()))),
+            Some("fileicon") => Ok(Self(IconInner::FileIcon(path.into()))),
+            Some("filetype") => Ok(Self(IconInner::FileType(path))),
+            Some(kind) => Err(D::Error::custom(format!("unknown icon type `{kind}`"))),
+        }
+    }
+}
+
+impl Error {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl Icon {
     /// Create a new icon using the image at the given path.
     ///
@@ -296,6 +577,28 @@ impl Icon {
         Self(IconInner::Image(path.into()))
     }
 
+    /// Create a new icon using one of two images depending on the Alfred theme.
+    ///
+    /// The `dark` image is used if the current Alfred theme has a dark
+    /// background, see [`env::is_dark_theme`]. Otherwise, including when the
+    /// theme cannot be determined, the `light` image is used. The paths can be
+    /// relative to the workflow directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Icon;
+    /// let icon = Icon::themed("./assets/icon-light.png", "./assets/icon-dark.png");
+    /// ```
+    #[cfg(feature = "env")]
+    pub fn themed(light: impl Into<PathBuf>, dark: impl Into<PathBuf>) -> Self {
+        if env::is_dark_theme() == Some(true) {
+            Self::with_image(dark)
+        } else {
+            Self::with_image(light)
+        }
+    }
+
     /// Create a new icon based on the file provided.
     ///
     /// This path can be relative to the workflow directory.
@@ -338,11 +641,292 @@ impl Icon {
     pub fn with_type(uti: impl Into<String>) -> Self {
         Self(IconInner::FileType(uti.into()))
     }
+
+    /// Create a new icon using the icon for the given [UTI][uti], checking
+    /// that the UTI is a known one.
+    ///
+    /// [`Icon::with_type`] accepts any string, so a typo like `public.jpg`
+    /// silently results in no icon. This function instead returns an error if
+    /// the UTI is not in a list of common system UTIs. Use
+    /// [`Icon::with_type`] for UTIs that are not in the list, e.g. ones
+    /// declared by third party applications.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Icon;
+    /// assert!(Icon::try_with_type("public.jpeg").is_ok());
+    /// assert!(Icon::try_with_type("public.jpg").is_err());
+    /// ```
+    ///
+    /// [uti]: https://en.wikipedia.org/wiki/Uniform_Type_Identifier
+    pub fn try_with_type(uti: impl Into<String>) -> Result<Self, Error> {
+        let uti = uti.into();
+        if !KNOWN_UTIS.contains(&uti.as_str()) {
+            return Err(Error::new(format!("unrecognized UTI `{uti}`")));
+        }
+        Ok(Self::with_type(uti))
+    }
+
+    /// Create a new icon using the system icon for the given file extension.
+    ///
+    /// The extension is mapped to an Apple [Uniform Type Identifier
+    /// (UTI)][uti] and used as in [`Icon::with_type`]. Unknown extensions fall
+    /// back to `public.data`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Icon;
+    /// let icon = Icon::with_extension("pdf");
+    /// assert_eq!(icon, Icon::with_type("com.adobe.pdf"));
+    /// ```
+    ///
+    /// [uti]: https://en.wikipedia.org/wiki/Uniform_Type_Identifier
+    pub fn with_extension(ext: &str) -> Self {
+        Self::with_type(uti_for_extension(ext))
+    }
+
+    /// Create a new icon from image data, for example a generated PNG.
+    ///
+    /// The data is written to a file named after a hash of its contents in the
+    /// `icons` subdirectory of the workflow cache directory. If the same data
+    /// has already been written then the existing file is reused, so this is
+    /// cheap to call on every run of the workflow.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ext` is empty or contains a path separator or a
+    /// `.`, if the workflow cache directory is not set, or if the file could not
+    /// be written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use powerpack::Icon;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let png = Vec::new();
+    /// let icon = Icon::from_bytes(&png, "png")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "env")]
+    pub fn from_bytes(bytes: &[u8], ext: &str) -> io::Result<Self> {
+        if ext.is_empty() || ext.contains(|c| c == '.' || std::path::is_separator(c)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid icon extension `{ext}`"),
+            ));
+        }
+        let dir = env::workflow_cache_dir()?.join("icons");
+        let path = dir.join(format!("{:016x}.{ext}", fnv1a(bytes)));
+        if !path.exists() {
+            fs::create_dir_all(&dir)?;
+            let tmp = path.with_extension(format!("{}.tmp", process::id()));
+            fs::write(&tmp, bytes)?;
+            fs::rename(&tmp, &path)?;
+        }
+        Ok(Self::with_image(path))
+    }
+}
+
+/// Create an image icon from a path, see [`Icon::with_image`].
+///
+/// Strings are always treated as paths, use [`Icon::with_type`] for UTIs.
+impl From<&str> for Icon {
+    fn from(path: &str) -> Self {
+        Self::with_image(path)
+    }
+}
+
+/// Create an image icon from a path, see [`Icon::with_image`].
+///
+/// Strings are always treated as paths, use [`Icon::with_type`] for UTIs.
+impl From<String> for Icon {
+    fn from(path: String) -> Self {
+        Self::with_image(path)
+    }
+}
+
+/// Create an image icon from a path, see [`Icon::with_image`].
+impl From<PathBuf> for Icon {
+    fn from(path: PathBuf) -> Self {
+        Self::with_image(path)
+    }
+}
+
+/// A simple stable hash used for content addressing files.
+#[cfg(feature = "env")]
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
 }
 
-impl Default for Kind {
-    fn default() -> Self {
-        Self::Default
+/// Common system Uniform Type Identifiers (UTIs).
+const KNOWN_UTIS: &[&str] = &[
+    // Base types
+    "public.item",
+    "public.content",
+    "public.data",
+    "public.composite-content",
+    "public.folder",
+    "public.directory",
+    "public.volume",
+    "public.symlink",
+    "public.executable",
+    "public.unix-executable",
+    "public.url",
+    "public.file-url",
+    "public.archive",
+    "public.text",
+    "public.image",
+    "public.audio",
+    "public.movie",
+    "public.video",
+    "public.audiovisual-content",
+    "public.contact",
+    "public.vcard",
+    "public.calendar-event",
+    "public.font",
+    "public.script",
+    "public.source-code",
+    "com.apple.bundle",
+    "com.apple.package",
+    "com.apple.application",
+    "com.apple.application-bundle",
+    "com.apple.alias-file",
+    "com.apple.mount-point",
+    "com.apple.icns",
+    "com.apple.systempreference.prefpane",
+    "com.apple.keynote.key",
+    "com.apple.iwork.pages.pages",
+    "com.apple.iwork.numbers.numbers",
+    // Documents
+    "com.adobe.pdf",
+    "public.plain-text",
+    "public.utf8-plain-text",
+    "public.rtf",
+    "net.daringfireball.markdown",
+    "public.html",
+    "public.xml",
+    "public.json",
+    "public.yaml",
+    "public.comma-separated-values-text",
+    "public.tab-separated-values-text",
+    "com.microsoft.word.doc",
+    "org.openxmlformats.wordprocessingml.document",
+    "com.microsoft.excel.xls",
+    "org.openxmlformats.spreadsheetml.sheet",
+    "com.microsoft.powerpoint.ppt",
+    "org.openxmlformats.presentationml.presentation",
+    // Images
+    "public.jpeg",
+    "public.png",
+    "com.compuserve.gif",
+    "public.tiff",
+    "com.microsoft.bmp",
+    "com.microsoft.ico",
+    "public.heic",
+    "org.webmproject.webp",
+    "public.svg-image",
+    // Audio and video
+    "public.mp3",
+    "com.microsoft.waveform-audio",
+    "public.aac-audio",
+    "com.apple.m4a-audio",
+    "public.mpeg-4",
+    "com.apple.quicktime-movie",
+    "public.avi",
+    // Archives
+    "public.zip-archive",
+    "org.gnu.gnu-zip-archive",
+    "public.tar-archive",
+    "com.apple.disk-image-udif",
+    // Source code
+    "public.shell-script",
+    "public.python-script",
+    "public.ruby-script",
+    "public.perl-script",
+    "public.php-script",
+    "com.netscape.javascript-source",
+    "public.swift-source",
+    "public.c-source",
+    "public.c-header",
+    "public.c-plus-plus-source",
+    "public.c-plus-plus-header",
+    "public.objective-c-source",
+    "com.sun.java-source",
+];
+
+/// Returns the UTI for a file extension, ignoring case and a leading dot.
+fn uti_for_extension(ext: &str) -> &'static str {
+    let ext = ext.trim_start_matches('.').to_ascii_lowercase();
+    match ext.as_str() {
+        // Documents
+        "pdf" => "com.adobe.pdf",
+        "txt" | "text" => "public.plain-text",
+        "rtf" => "public.rtf",
+        "md" | "markdown" => "net.daringfireball.markdown",
+        "html" | "htm" => "public.html",
+        "xml" => "public.xml",
+        "json" => "public.json",
+        "yaml" | "yml" => "public.yaml",
+        "csv" => "public.comma-separated-values-text",
+        "tsv" => "public.tab-separated-values-text",
+        "doc" => "com.microsoft.word.doc",
+        "docx" => "org.openxmlformats.wordprocessingml.document",
+        "xls" => "com.microsoft.excel.xls",
+        "xlsx" => "org.openxmlformats.spreadsheetml.sheet",
+        "ppt" => "com.microsoft.powerpoint.ppt",
+        "pptx" => "org.openxmlformats.presentationml.presentation",
+
+        // Images
+        "jpg" | "jpeg" => "public.jpeg",
+        "png" => "public.png",
+        "gif" => "com.compuserve.gif",
+        "tif" | "tiff" => "public.tiff",
+        "bmp" => "com.microsoft.bmp",
+        "ico" => "com.microsoft.ico",
+        "heic" => "public.heic",
+        "webp" => "org.webmproject.webp",
+        "svg" => "public.svg-image",
+
+        // Audio and video
+        "mp3" => "public.mp3",
+        "wav" => "com.microsoft.waveform-audio",
+        "aac" => "public.aac-audio",
+        "m4a" => "com.apple.m4a-audio",
+        "mp4" => "public.mpeg-4",
+        "mov" => "com.apple.quicktime-movie",
+        "avi" => "public.avi",
+
+        // Archives
+        "zip" => "public.zip-archive",
+        "gz" | "gzip" => "org.gnu.gnu-zip-archive",
+        "tar" => "public.tar-archive",
+        "dmg" => "com.apple.disk-image-udif",
+
+        // Source code
+        "sh" | "bash" | "zsh" => "public.shell-script",
+        "py" => "public.python-script",
+        "rb" => "public.ruby-script",
+        "pl" => "public.perl-script",
+        "php" => "public.php-script",
+        "js" => "com.netscape.javascript-source",
+        "swift" => "public.swift-source",
+        "c" => "public.c-source",
+        "h" => "public.c-header",
+        "cc" | "cpp" | "cxx" => "public.c-plus-plus-source",
+        "hh" | "hpp" | "hxx" => "public.c-plus-plus-header",
+        "m" => "public.objective-c-source",
+        "java" => "com.sun.java-source",
+        "rs" | "go" | "ts" | "toml" => "public.source-code",
+
+        // Applications
+        "app" => "com.apple.application-bundle",
+
+        _ => "public.data",
     }
 }
 
@@ -373,6 +957,11 @@ impl Modifier {
     }
 
     /// The subtitle for when this modifier is activated.
+    ///
+    /// If no subtitle is set then Alfred keeps showing the item's subtitle
+    /// when the modifier is pressed. To hide the subtitle instead set it to
+    /// an empty string, which is serialized as `"subtitle": ""` rather than
+    /// being omitted.
     #[must_use]
     pub fn subtitle(mut self, subtitle: impl Into<String>) -> Self {
         self.data.subtitle = Some(subtitle.into());
@@ -388,8 +977,8 @@ impl Modifier {
 
     /// The icon for when this modifier is activated.
     #[must_use]
-    pub fn icon(mut self, arg: Icon) -> Self {
-        self.data.icon = Some(arg);
+    pub fn icon(mut self, icon: impl Into<Icon>) -> Self {
+        self.data.icon = Some(icon.into());
         self
     }
 
@@ -399,6 +988,29 @@ impl Modifier {
         self.data.valid = Some(valid);
         self
     }
+
+    /// The type of item for when this modifier is activated.
+    #[must_use]
+    pub fn kind(mut self, kind: Kind) -> Self {
+        self.data.kind = kind;
+        self
+    }
+
+    /// The text the user will get when copying the selected result row with ⌘C
+    /// while this modifier is activated.
+    #[must_use]
+    pub fn copy_text(mut self, copy: impl Into<String>) -> Self {
+        self.data.text.get_or_insert_with(Text::default).copy = Some(copy.into());
+        self
+    }
+
+    /// The text the user will get when displaying large type with ⌘L while
+    /// this modifier is activated.
+    #[must_use]
+    pub fn large_type_text(mut self, large_type: impl Into<String>) -> Self {
+        self.data.text.get_or_insert_with(Text::default).large_type = Some(large_type.into());
+        self
+    }
 }
 
 impl Item {
@@ -411,10 +1023,69 @@ impl Item {
         }
     }
 
+    /// Create a new item that navigates deeper instead of being actioned.
+    ///
+    /// This is useful for drill-down menus. The item is not valid, so pressing
+    /// ↵ fills Alfred's search field with `autocomplete` instead of actioning
+    /// the item, and the subtitle tells the user as much. The subtitle can be
+    /// replaced using [`subtitle`][Self::subtitle].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Item;
+    /// let item = Item::new("Settings")
+    ///     .subtitle("Press ↵ to open")
+    ///     .autocomplete("settings ")
+    ///     .valid(false);
+    /// assert_eq!(Item::navigation("Settings", "settings "), item);
+    /// ```
+    #[must_use]
+    pub fn navigation(title: impl Into<String>, autocomplete: impl Into<String>) -> Self {
+        Self::new(title)
+            .subtitle("Press ↵ to open")
+            .autocomplete(autocomplete)
+            .invalid()
+    }
+
     /// Set the subtitle for this item.
     #[must_use]
     pub fn subtitle(mut self, subtitle: impl Into<String>) -> Self {
         self.subtitle = Some(subtitle.into());
+        if self.match_title_and_subtitle {
+            self = self.match_title_and_subtitle();
+        }
+        self
+    }
+
+    /// Make the title and subtitles fit on a single line.
+    ///
+    /// Alfred displays the title and subtitle on a single line, so embedded
+    /// newlines and tabs make the result row look broken. This replaces line
+    /// breaks and tabs in the title, subtitle, and modifier subtitles with a
+    /// single space and trims leading and trailing whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Item;
+    /// let item = Item::new("  Hello\n\nworld!\t").subtitle("a\r\nb").sanitize();
+    /// assert_eq!(item, Item::new("Hello world!").subtitle("a b"));
+    /// ```
+    #[must_use]
+    pub fn sanitize(mut self) -> Self {
+        self.title = single_line(&self.title);
+        if let Some(subtitle) = &mut self.subtitle {
+            *subtitle = single_line(subtitle);
+        }
+        for data in self.modifiers.values_mut() {
+            if let Some(subtitle) = &mut data.subtitle {
+                *subtitle = single_line(subtitle);
+            }
+        }
+        if self.match_title_and_subtitle {
+            self = self.match_title_and_subtitle();
+        }
         self
     }
 
@@ -434,6 +1105,35 @@ impl Item {
         self
     }
 
+    /// Set the UID for this item to its current argument.
+    ///
+    /// Using the argument as the UID is a common convention since it usually
+    /// identifies the item, and it means Alfred learns from the user's
+    /// selections without having to remember to set a UID separately. If
+    /// multiple arguments are set they are joined with a tab. If no argument
+    /// is set this does nothing, so this must be called after
+    /// [`arg`][Self::arg] or [`args`][Self::args].
+    ///
+    /// Omit the UID instead if you want Alfred to always show the items in the
+    /// order they are output, see [`uid`][Self::uid].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Item;
+    /// let item = Item::new("Desktop").arg("~/Desktop").uid_from_arg();
+    /// assert_eq!(item, Item::new("Desktop").arg("~/Desktop").uid("~/Desktop"));
+    /// ```
+    #[must_use]
+    pub fn uid_from_arg(mut self) -> Self {
+        match &self.arg {
+            Some(Arg::One(arg)) => self.uid = Some(arg.clone()),
+            Some(Arg::Many(args)) => self.uid = Some(args.join("\t")),
+            None => {}
+        }
+        self
+    }
+
     /// Set the argument which is passed through the workflow to the connected
     /// output action.
     ///
@@ -447,6 +1147,30 @@ impl Item {
         self
     }
 
+    /// Set the argument from an OS string, e.g. a file path.
+    ///
+    /// Script filter output is JSON which can only contain valid UTF-8, so any
+    /// invalid sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    /// This means a path that is not valid UTF-8 will not be passed through
+    /// the workflow unchanged.
+    #[must_use]
+    pub fn arg_os(self, arg: impl AsRef<OsStr>) -> Self {
+        self.arg(arg.as_ref().to_string_lossy())
+    }
+
+    /// Set the argument to the given value serialized as JSON.
+    ///
+    /// This is useful for passing structured data to another script filter in
+    /// the workflow, which can read it back using [`arg_json()`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value cannot be serialized as JSON, e.g. a map with
+    /// non-string keys.
+    pub fn arg_json<T: Serialize + ?Sized>(self, value: &T) -> serde_json::Result<Self> {
+        Ok(self.arg(serde_json::to_string(value)?))
+    }
+
     /// Set the arguments which are passed through the workflow to the connected
     /// output action.
     ///
@@ -466,8 +1190,8 @@ impl Item {
     /// Workflows are run from their workflow folder, so you can reference icons
     /// stored in your workflow relatively.
     #[must_use]
-    pub fn icon(mut self, icon: Icon) -> Self {
-        self.icon = Some(icon);
+    pub fn icon(mut self, icon: impl Into<Icon>) -> Self {
+        self.icon = Some(icon.into());
         self
     }
 
@@ -486,6 +1210,17 @@ impl Item {
         self
     }
 
+    /// Mark this item as not valid.
+    ///
+    /// This is an alias for `.valid(false)` and is useful for purely
+    /// informational items that should never be actioned. Unlike leaving the
+    /// valid attribute unset, which Alfred treats as valid, this always emits
+    /// `"valid": false`.
+    #[must_use]
+    pub fn invalid(self) -> Self {
+        self.valid(false)
+    }
+
     /// Set the text that Alfred will match against.
     ///
     /// This field enables you to define what Alfred matches against when the
@@ -498,6 +1233,45 @@ impl Item {
     #[must_use]
     pub fn matches(mut self, matches: impl Into<String>) -> Self {
         self.matches = Some(matches.into());
+        self.match_title_and_subtitle = false;
+        self
+    }
+
+    /// Set the text that Alfred will match against, including a copy without
+    /// diacritics.
+    ///
+    /// Alfred's matching stops being diacritic insensitive as soon as the
+    /// query contains a diacritic. This stores `"{matches} {folded}"`, where
+    /// `folded` is the result of [`fold_diacritics`], so that both `"café"`
+    /// and `"cafe"` match. If there are no diacritics the text is stored
+    /// unchanged.
+    #[must_use]
+    pub fn matches_folded(self, matches: impl Into<String>) -> Self {
+        let matches = matches.into();
+        let folded = fold_diacritics(&matches);
+        if folded == matches {
+            self.matches(matches)
+        } else {
+            self.matches(format!("{matches} {folded}"))
+        }
+    }
+
+    /// Match against both the title and the subtitle.
+    ///
+    /// This sets the match field to `"{title} {subtitle}"`, and keeps it up to
+    /// date if the subtitle is set afterwards. Text set explicitly using
+    /// [`.matches()`][Item::matches] always wins, whether it is set before or
+    /// after calling this.
+    #[must_use]
+    pub fn match_title_and_subtitle(mut self) -> Self {
+        if self.matches.is_some() && !self.match_title_and_subtitle {
+            return self;
+        }
+        self.matches = Some(match &self.subtitle {
+            Some(subtitle) => format!("{} {}", self.title, subtitle),
+            None => self.title.clone(),
+        });
+        self.match_title_and_subtitle = true;
         self
     }
 
@@ -512,6 +1286,28 @@ impl Item {
         self
     }
 
+    /// Set the autocomplete value for this item to the given base followed by
+    /// a trailing space.
+    ///
+    /// This is useful for "type to drill down" interfaces where completing the
+    /// item should let the user continue typing the next part of the query
+    /// straight away. Such items are often used purely for navigation, in
+    /// which case you probably also want to set [`valid(false)`][Self::valid]
+    /// so that pressing return autocompletes instead of actioning the item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Item;
+    /// let item = Item::new("Settings")
+    ///     .autocomplete_prefix("settings")
+    ///     .valid(false);
+    /// ```
+    #[must_use]
+    pub fn autocomplete_prefix(self, base: &str) -> Self {
+        self.autocomplete(format!("{base} "))
+    }
+
     /// Set the type of item.
     #[must_use]
     pub fn kind(mut self, kind: Kind) -> Self {
@@ -543,8 +1339,8 @@ impl Item {
     /// Set the Quick Look URL for the item.
     ///
     /// This will be visible if the user uses the Quick Look feature within
-    /// Alfred (tapping shift, or ⌘Y). This field will also accept a file path,
-    /// both absolute and relative to home using ~/.
+    /// Alfred (tapping shift, or ⌘Y). To preview a file use
+    /// [`Item::quicklook_file`] instead.
     ///
     /// If absent, Alfred will attempt to use the arg as the quicklook URL.
     #[must_use]
@@ -553,6 +1349,29 @@ impl Item {
         self
     }
 
+    /// Set the file to show if the user uses Quick Look (⌘Y).
+    ///
+    /// A leading `~` is expanded to the home directory and relative paths are
+    /// made absolute using the current directory, since Alfred would not be
+    /// able to resolve them. This sets the same field as
+    /// [`Item::quicklook_url`].
+    #[must_use]
+    pub fn quicklook_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let mut path = path.into();
+        if let Ok(rest) = path.strip_prefix("~") {
+            if let Some(home) = std::env::var_os("HOME") {
+                path = PathBuf::from(home).join(rest);
+            }
+        }
+        if path.is_relative() {
+            if let Ok(cwd) = std::env::current_dir() {
+                path = cwd.join(path);
+            }
+        }
+        self.quicklook_url = Some(path.to_string_lossy().into_owned());
+        self
+    }
+
     /// Add a modifier key configuration.
     ///
     /// This gives you control over how the modifier keys react. For example you
@@ -607,18 +1426,302 @@ impl Item {
         self.action = action.into();
         self
     }
+
+    /// Set a variable which is passed out of the script filter if this item
+    /// is actioned.
+    ///
+    /// Item variables are merged with the [`Output`] session variables, with
+    /// the item variables taking precedence.
+    #[must_use]
+    pub fn variable(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(key.into(), value.into());
+        self
+    }
 }
 
-fn duration_as_secs<S>(duration: &Option<Duration>, s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
+/// Getters for inspecting an item after it has been built.
+///
+/// Most getters are prefixed with `get_` because the unprefixed names are used
+/// by the builder methods.
+impl Item {
+    /// Returns the title.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the subtitle.
+    pub fn get_subtitle(&self) -> Option<&str> {
+        self.subtitle.as_deref()
+    }
+
+    /// Returns the UID.
+    pub fn get_uid(&self) -> Option<&str> {
+        self.uid.as_deref()
+    }
+
+    /// Returns the argument if a single argument was set.
+    ///
+    /// Use [`get_args`][Self::get_args] to get multiple arguments.
+    pub fn get_arg(&self) -> Option<&str> {
+        match &self.arg {
+            Some(Arg::One(arg)) => Some(arg),
+            _ => None,
+        }
+    }
+
+    /// Returns all the arguments.
+    ///
+    /// This is empty if no argument was set, and contains a single element if
+    /// one was set using [`arg`][Self::arg].
+    pub fn get_args(&self) -> &[String] {
+        match &self.arg {
+            Some(Arg::One(arg)) => std::slice::from_ref(arg),
+            Some(Arg::Many(args)) => args,
+            None => &[],
+        }
+    }
+
+    /// Returns the icon.
+    pub fn get_icon(&self) -> Option<&Icon> {
+        self.icon.as_ref()
+    }
+
+    /// Returns whether the item is valid, if this was set.
+    pub fn get_valid(&self) -> Option<bool> {
+        self.valid
+    }
+
+    /// Returns the text that Alfred will match against.
+    pub fn get_matches(&self) -> Option<&str> {
+        self.matches.as_deref()
+    }
+
+    /// Returns the autocomplete value.
+    pub fn get_autocomplete(&self) -> Option<&str> {
+        self.autocomplete.as_deref()
+    }
+
+    /// Returns the type of item.
+    pub fn get_kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Returns the text copied when the user copies the item (⌘C).
+    pub fn get_copy_text(&self) -> Option<&str> {
+        self.text.as_ref()?.copy.as_deref()
+    }
+
+    /// Returns the text displayed in large type (⌘L).
+    pub fn get_large_type_text(&self) -> Option<&str> {
+        self.text.as_ref()?.large_type.as_deref()
+    }
+
+    /// Returns the Quick Look URL.
+    pub fn get_quicklook_url(&self) -> Option<&str> {
+        self.quicklook_url.as_deref()
+    }
+
+    /// Returns the value of a variable set on this item.
+    pub fn get_variable(&self, key: &str) -> Option<&str> {
+        self.variables.get(key).map(String::as_str)
+    }
+}
+
+/// Setters for modifying an item in place, e.g. in a post-processing pass over
+/// a list of items.
+///
+/// Each setter behaves the same as the builder method of the same name
+/// without the `set_` prefix.
+///
+/// # Examples
+///
+/// ```
+/// # use powerpack::Item;
+/// let mut items = vec![Item::new("a"), Item::new("b")];
+/// for (i, item) in items.iter_mut().enumerate() {
+///     item.set_subtitle(format!("Result {}", i + 1)).set_valid(false);
+/// }
+/// ```
+impl Item {
+    fn update(&mut self, f: impl FnOnce(Self) -> Self) -> &mut Self {
+        *self = f(std::mem::take(self));
+        self
+    }
+
+    /// Set the title, see [`Item::new`].
+    pub fn set_title(&mut self, title: impl Into<String>) -> &mut Self {
+        self.title = title.into();
+        if self.match_title_and_subtitle {
+            self.update(Self::match_title_and_subtitle);
+        }
+        self
+    }
+
+    /// Set the subtitle, see [`Item::subtitle`].
+    pub fn set_subtitle(&mut self, subtitle: impl Into<String>) -> &mut Self {
+        self.update(|item| item.subtitle(subtitle))
+    }
+
+    /// Set the UID, see [`Item::uid`].
+    pub fn set_uid(&mut self, uid: impl Into<String>) -> &mut Self {
+        self.update(|item| item.uid(uid))
+    }
+
+    /// Set the argument, see [`Item::arg`].
+    pub fn set_arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.update(|item| item.arg(arg))
+    }
+
+    /// Set the icon, see [`Item::icon`].
+    pub fn set_icon(&mut self, icon: impl Into<Icon>) -> &mut Self {
+        self.update(|item| item.icon(icon))
+    }
+
+    /// Set whether this item is valid, see [`Item::valid`].
+    pub fn set_valid(&mut self, valid: bool) -> &mut Self {
+        self.update(|item| item.valid(valid))
+    }
+
+    /// Set the text that Alfred will match against, see [`Item::matches`].
+    pub fn set_matches(&mut self, matches: impl Into<String>) -> &mut Self {
+        self.update(|item| item.matches(matches))
+    }
+
+    /// Set the autocomplete value, see [`Item::autocomplete`].
+    pub fn set_autocomplete(&mut self, autocomplete: impl Into<String>) -> &mut Self {
+        self.update(|item| item.autocomplete(autocomplete))
+    }
+
+    /// Set the type of item, see [`Item::kind`].
+    pub fn set_kind(&mut self, kind: Kind) -> &mut Self {
+        self.update(|item| item.kind(kind))
+    }
+
+    /// Set a modifier, see [`Item::modifier`].
+    pub fn set_modifier(&mut self, modifier: Modifier) -> &mut Self {
+        self.update(|item| item.modifier(modifier))
+    }
+
+    /// Set the copied text, see [`Item::copy_text`].
+    pub fn set_copy_text(&mut self, text: impl Into<String>) -> &mut Self {
+        self.update(|item| item.copy_text(text))
+    }
+
+    /// Set the large type text, see [`Item::large_type_text`].
+    pub fn set_large_type_text(&mut self, text: impl Into<String>) -> &mut Self {
+        self.update(|item| item.large_type_text(text))
+    }
+
+    /// Set the Quick Look URL, see [`Item::quicklook_url`].
+    pub fn set_quicklook_url(&mut self, url: impl Into<String>) -> &mut Self {
+        self.update(|item| item.quicklook_url(url))
+    }
+
+    /// Set the Quick Look file, see [`Item::quicklook_file`].
+    pub fn set_quicklook_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.update(|item| item.quicklook_file(path))
+    }
+
+    /// Set a variable, see [`Item::variable`].
+    pub fn set_variable(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.update(|item| item.variable(key, value))
+    }
+}
+
+fn duration_as_secs<S>(duration: &Option<Duration>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
     match duration {
-        Some(d) => s.serialize_f32(d.as_secs_f32()),
+        // Round to milliseconds so that durations created from an `f32`, e.g.
+        // using `rerun_secs`, don't serialize with rounding noise
+        Some(d) => s.serialize_f64((d.as_secs_f64() * 1000.0).round() / 1000.0),
         None => unreachable!(),
     }
 }
 
+fn duration_from_secs<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secs = Option::<f64>::deserialize(d)?;
+    secs.map(|secs| Duration::try_from_secs_f64(secs).map_err(D::Error::custom))
+        .transpose()
+}
+
+impl<'a> ItemRef<'a> {
+    /// Create a new borrowed item with the given title.
+    #[must_use]
+    pub fn new(title: &'a str) -> Self {
+        Self {
+            title,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the title.
+    pub fn title(&self) -> &'a str {
+        self.title
+    }
+
+    /// Set the subtitle, see [`Item::subtitle`].
+    #[must_use]
+    pub fn subtitle(mut self, subtitle: &'a str) -> Self {
+        self.subtitle = Some(subtitle);
+        self
+    }
+
+    /// Set the UID, see [`Item::uid`].
+    #[must_use]
+    pub fn uid(mut self, uid: &'a str) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Set the argument, see [`Item::arg`].
+    #[must_use]
+    pub fn arg(mut self, arg: &'a str) -> Self {
+        self.arg = Some(arg);
+        self
+    }
+
+    /// Set whether this item is valid, see [`Item::valid`].
+    #[must_use]
+    pub fn valid(mut self, valid: bool) -> Self {
+        self.valid = Some(valid);
+        self
+    }
+
+    /// Set the text that Alfred will match against, see [`Item::matches`].
+    #[must_use]
+    pub fn matches(mut self, matches: &'a str) -> Self {
+        self.matches = Some(matches);
+        self
+    }
+
+    /// Set the autocomplete value, see [`Item::autocomplete`].
+    #[must_use]
+    pub fn autocomplete(mut self, autocomplete: &'a str) -> Self {
+        self.autocomplete = Some(autocomplete);
+        self
+    }
+}
+
+impl From<ItemRef<'_>> for Item {
+    fn from(item: ItemRef<'_>) -> Self {
+        Self {
+            title: item.title.to_owned(),
+            subtitle: item.subtitle.map(str::to_owned),
+            uid: item.uid.map(str::to_owned),
+            arg: item.arg.map(|arg| Arg::One(arg.to_owned())),
+            valid: item.valid,
+            matches: item.matches.map(str::to_owned),
+            autocomplete: item.autocomplete.map(str::to_owned),
+            ..Self::default()
+        }
+    }
+}
+
 impl Output {
     /// Create a new output.
     #[must_use]
@@ -632,11 +1735,62 @@ impl Output {
     /// value of 0.1 to 5.0 seconds. The script will only be re-run if the
     /// script filter is still active and the user hasn't changed the state of
     /// the filter by typing and triggering a re-run.
+    ///
+    /// A warning is written to stderr if the duration is outside this range.
     pub fn rerun(&mut self, duration: Duration) -> &mut Self {
+        let secs = duration.as_secs_f64();
+        if !(0.1..=5.0).contains(&secs) {
+            eprintln!("warning: rerun of {secs}s is outside Alfred's range of 0.1s to 5.0s");
+        }
         self.rerun = Some(duration);
         self
     }
 
+    /// Set the rerun value in seconds.
+    ///
+    /// This is the same as [`rerun`][Self::rerun] but takes the number of
+    /// seconds, which is how the value is expressed in Alfred.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::Output;
+    /// Output::new().rerun_secs(0.3);
+    /// ```
+    pub fn rerun_secs(&mut self, secs: f32) -> &mut Self {
+        match Duration::try_from_secs_f32(secs) {
+            Ok(duration) => self.rerun(duration),
+            Err(_) => {
+                eprintln!("warning: ignoring invalid rerun of {secs}s");
+                self
+            }
+        }
+    }
+
+    /// Set how long Alfred should cache these results.
+    ///
+    /// Alfred will reuse the output for the same query instead of running the
+    /// script filter again until `duration` has elapsed. If `loose_reload` is
+    /// `true` then Alfred shows the cached results immediately while running
+    /// the script filter in the background to refresh them. Alfred accepts a
+    /// duration of 5 to 86400 seconds, a warning is written to stderr
+    /// otherwise.
+    ///
+    /// This requires Alfred 5.5 or later.
+    pub fn cache(&mut self, duration: Duration, loose_reload: bool) -> &mut Self {
+        let seconds = duration.as_secs();
+        if !(5..=86400).contains(&seconds) {
+            eprintln!(
+                "warning: cache duration of {seconds}s is outside Alfred's range of 5s to 86400s"
+            );
+        }
+        self.cache = Some(ResultCache {
+            seconds,
+            loose_reload,
+        });
+        self
+    }
+
     /// Set the skip knowledge value.
     ///
     /// This allows you to set `uid` and preserve the item order while allowing
@@ -647,6 +1801,31 @@ impl Output {
         self
     }
 
+    /// Set a session variable.
+    ///
+    /// Session variables are available to the script filter as environment
+    /// variables when it is rerun (see [`rerun`][Self::rerun]) and are passed
+    /// out of the script filter when an item is actioned. This makes them
+    /// useful for persisting state between runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use powerpack::Output;
+    /// let query_count = powerpack::env::var("query_count")
+    ///     .and_then(|s| s.parse::<u32>().ok())
+    ///     .unwrap_or(0);
+    ///
+    /// Output::new()
+    ///     .rerun(Duration::from_secs(1))
+    ///     .variable("query_count", (query_count + 1).to_string());
+    /// ```
+    pub fn variable(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.variables.insert(key.into(), value.into());
+        self
+    }
+
     /// Extend the list of items to output.
     pub fn items<I>(&mut self, iter: I) -> &mut Self
     where
@@ -656,16 +1835,609 @@ impl Output {
         self
     }
 
+    /// Merge another output into this one.
+    ///
+    /// This is useful when several independent sources contribute items to
+    /// the same script filter result. Values are combined as follows:
+    /// - `items` from `other` are appended after the existing items.
+    /// - `variables` are merged, with the values from `other` taking
+    ///   precedence.
+    /// - `rerun` is the shorter of the two intervals, so that the script
+    ///   filter is rerun as soon as any source needs it.
+    /// - `cache` is the one with the shorter duration.
+    /// - `skip_knowledge` and the placeholder item from `other` are used if
+    ///   they are set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use powerpack::{Item, Output};
+    /// let mut output = Output::new();
+    /// output.items([Item::new("a")]).variable("source", "a");
+    ///
+    /// let mut other = Output::new();
+    /// other
+    ///     .rerun(Duration::from_secs(1))
+    ///     .items([Item::new("b")])
+    ///     .variable("source", "b");
+    ///
+    /// output.merge(other);
+    /// ```
+    pub fn merge(&mut self, other: Output) -> &mut Self {
+        let Output {
+            rerun,
+            skip_knowledge,
+            variables,
+            cache,
+            items,
+            placeholder,
+        } = other;
+        self.rerun = match (self.rerun, rerun) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.cache = match (self.cache.take(), cache) {
+            (Some(a), Some(b)) => Some(if b.seconds < a.seconds { b } else { a }),
+            (a, b) => a.or(b),
+        };
+        self.skip_knowledge = skip_knowledge.or(self.skip_knowledge);
+        self.placeholder = placeholder.or(self.placeholder.take());
+        self.variables.extend(variables);
+        self.items.extend(items);
+        self
+    }
+
+    /// Check the items for common mistakes.
+    ///
+    /// Currently this checks that no two items share the same UID, which
+    /// causes Alfred's knowledge and sorting to behave unexpectedly. Any
+    /// problems are written as warnings to stderr, which Alfred displays in
+    /// the workflow debugger.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::{Item, Output};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// Output::new()
+    ///     .items([Item::new("a").uid("x"), Item::new("b").uid("x")])
+    ///     .validate()
+    ///     .write(std::io::stdout())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn validate(&self) -> &Self {
+        let mut seen = HashMap::new();
+        let mut dups = Vec::new();
+        for uid in self.items.iter().filter_map(|item| item.uid.as_deref()) {
+            let count = seen.entry(uid).or_insert(0);
+            *count += 1;
+            if *count == 2 {
+                dups.push(uid);
+            }
+        }
+        if !dups.is_empty() {
+            eprintln!("warning: items with duplicate UIDs: {dups:?}");
+        }
+        self
+    }
+
+    /// Set an item to show instead if there are no items.
+    ///
+    /// The placeholder is only used when writing the output, for example to
+    /// show a "No matches found" row.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use powerpack::{Item, Output};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// Output::new()
+    ///     .placeholder_when_empty(Item::new("No matches found").invalid())
+    ///     .write(&mut buf)?;
+    /// assert_eq!(
+    ///     buf,
+    ///     br#"{"items":[{"title":"No matches found","valid":false}]}"#
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn placeholder_when_empty(&mut self, item: Item) -> &mut Self {
+        self.placeholder = Some(item);
+        self
+    }
+
     /// Output this script filter to the given writer.
+    ///
+    /// Serialization performs many small writes, so an unbuffered writer like
+    /// [`io::stdout()`] should be wrapped in an [`io::BufWriter`].
     pub fn write<W: io::Write>(&self, w: W) -> serde_json::Result<()> {
-        serde_json::to_writer(w, self)
+        match &self.placeholder {
+            Some(item) if self.items.is_empty() => {
+                let mut output = self.clone();
+                output.items.push(item.clone());
+                serde_json::to_writer(w, &output)
+            }
+            _ => serde_json::to_writer(w, self),
+        }
+    }
+
+    /// Output the items as newline delimited JSON to the given writer.
+    ///
+    /// Each item is written as a compact JSON object on its own line, which
+    /// is useful for inspecting the items with tools like `grep` and `jq`.
+    /// **This is not a format that Alfred understands**, use
+    /// [`Output::write`] for that. Only the items are written, the other
+    /// script filter fields like the variables are not included.
+    pub fn write_ndjson<W: io::Write>(&self, mut w: W) -> serde_json::Result<()> {
+        let items = match &self.placeholder {
+            Some(item) if self.items.is_empty() => std::slice::from_ref(item),
+            _ => &self.items,
+        };
+        for item in items {
+            serde_json::to_writer(&mut w, item)?;
+            w.write_all(b"\n").map_err(serde_json::Error::io)?;
+        }
+        Ok(())
     }
 }
 
+impl Items {
+    /// Create a new empty collection of items.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the items.
+    pub fn iter(&self) -> std::slice::Iter<'_, Item> {
+        self.0.iter()
+    }
+
+    /// Keep only the first `n` items.
+    #[must_use]
+    pub fn truncate(mut self, n: usize) -> Self {
+        self.0.truncate(n);
+        self
+    }
+
+    /// Remove items with a UID that has already been seen.
+    ///
+    /// The first item with a given UID is kept. Items without a UID are
+    /// always kept.
+    #[must_use]
+    pub fn dedup_by_uid(mut self) -> Self {
+        let mut seen = HashSet::new();
+        self.0.retain(|item| match &item.uid {
+            Some(uid) => seen.insert(uid.clone()),
+            None => true,
+        });
+        self
+    }
+
+    /// Sort the items by the given score, highest first.
+    ///
+    /// The sort is stable, so items with equal scores keep their order.
+    #[must_use]
+    pub fn sorted_by_score<F, S>(mut self, mut f: F) -> Self
+    where
+        F: FnMut(&Item) -> S,
+        S: Ord,
+    {
+        self.0.sort_by_cached_key(|item| Reverse(f(item)));
+        self
+    }
+}
+
+impl From<Vec<Item>> for Items {
+    fn from(items: Vec<Item>) -> Self {
+        Self(items)
+    }
+}
+
+impl From<Items> for Vec<Item> {
+    fn from(items: Items) -> Self {
+        items.0
+    }
+}
+
+impl FromIterator<Item> for Items {
+    fn from_iter<I: IntoIterator<Item = Item>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Item> for Items {
+    fn extend<I: IntoIterator<Item = Item>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl IntoIterator for Items {
+    type Item = Item;
+    type IntoIter = std::vec::IntoIter<Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Items {
+    type Item = &'a Item;
+    type IntoIter = std::slice::Iter<'a, Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Loading {
+    /// Create a new loading helper using the given session variable name to
+    /// track the state.
+    #[must_use]
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            item: Item::new("Loading...").invalid(),
+            rerun: Duration::from_millis(500),
+        }
+    }
+
+    /// Set the item displayed while loading.
+    #[must_use]
+    pub fn item(mut self, item: Item) -> Self {
+        self.item = item;
+        self
+    }
+
+    /// Set the interval after which to rerun the script filter while
+    /// loading, defaults to 0.5 seconds.
+    #[must_use]
+    pub fn rerun(mut self, rerun: Duration) -> Self {
+        self.rerun = rerun;
+        self
+    }
+
+    /// Returns `true` if the script filter has not been rerun by a previous
+    /// loading output, i.e. the background job should be started.
+    pub fn is_first_run(&self) -> bool {
+        std::env::var_os(&self.key).is_none()
+    }
+
+    /// Build the output for this run.
+    ///
+    /// If the result is ready the items are output. Otherwise the loading
+    /// item is output and the script filter is set to rerun, with the session
+    /// variable set so that subsequent runs are not the first run.
+    pub fn output<I>(&self, result: Option<I>) -> Output
+    where
+        I: IntoIterator<Item = Item>,
+    {
+        let mut output = Output::new();
+        match result {
+            Some(items) => {
+                output.items(items);
+            }
+            None => {
+                output
+                    .rerun(self.rerun)
+                    .variable(&self.key, "1")
+                    .items([self.item.clone()]);
+            }
+        }
+        output
+    }
+}
+
+/// Remove diacritics from Latin characters, e.g. `"café"` becomes `"cafe"`.
+///
+/// Combining marks are removed and precomposed Latin characters are replaced
+/// with their unaccented form. Some characters are expanded, e.g. `"ß"`
+/// becomes `"ss"`. Characters from other scripts are left unchanged.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(powerpack::fold_diacritics("Crème Brûlée"), "Creme Brulee");
+/// ```
+pub fn fold_diacritics(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match fold_char(c) {
+            Some(folded) => out.push_str(folded),
+            None if ('\u{300}'..='\u{36f}').contains(&c) => {}
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Replace line breaks and tabs with a single space and trim.
+fn single_line(s: &str) -> String {
+    s.split(['\n', '\r', '\t'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the unaccented form of a precomposed Latin character.
+fn fold_char(c: char) -> Option<&'static str> {
+    let folded = match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => "C",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'ď' | 'đ' | 'ð' => "d",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => "G",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'Ĥ' | 'Ħ' => "H",
+        'ĥ' | 'ħ' => "h",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => "I",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'Ĵ' => "J",
+        'ĵ' => "j",
+        'Ķ' => "K",
+        'ķ' => "k",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => "L",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => "O",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ŕ' | 'Ŗ' | 'Ř' => "R",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' => "S",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'Ţ' | 'Ť' | 'Ŧ' => "T",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'Þ' => "TH",
+        'þ' => "th",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'Ŵ' => "W",
+        'ŵ' => "w",
+        'Ý' | 'Ÿ' | 'Ŷ' => "Y",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(folded)
+}
+
+/// Returns a [JSON Schema] describing the script filter output.
+///
+/// This can be used to validate captured or hand-written script filter
+/// output, for example in an editor.
+///
+/// This function requires the `schema` feature.
+///
+/// [JSON Schema]: https://json-schema.org
+#[cfg(feature = "schema")]
+pub fn json_schema() -> String {
+    include_str!("schema.json").to_owned()
+}
+
 /// Shortcut function to output a list of items to stdout.
 pub fn output<I>(items: I) -> serde_json::Result<()>
 where
     I: IntoIterator<Item = Item>,
 {
-    Output::new().items(items).write(io::stdout())
+    with_stdout(|w| output_to(w, items))
+}
+
+/// Call `f` with a buffered stdout and flush it afterwards.
+fn with_stdout<F>(f: F) -> serde_json::Result<()>
+where
+    F: FnOnce(&mut io::BufWriter<io::StdoutLock<'static>>) -> serde_json::Result<()>,
+{
+    let mut w = io::BufWriter::new(io::stdout().lock());
+    f(&mut w)?;
+    io::Write::flush(&mut w).map_err(serde_json::Error::io)
+}
+
+/// Shortcut function to output a list of items to the given writer.
+///
+/// This is useful for capturing the output in tests.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut buf = Vec::new();
+/// powerpack::output_to(&mut buf, [powerpack::Item::new("Hello world!")])?;
+/// assert_eq!(buf, br#"{"items":[{"title":"Hello world!"}]}"#);
+/// # Ok(())
+/// # }
+/// ```
+pub fn output_to<W, I>(w: W, items: I) -> serde_json::Result<()>
+where
+    W: io::Write,
+    I: IntoIterator<Item = Item>,
+{
+    Output::new().items(items).write(w)
+}
+
+/// Shortcut function to output a list of fallible items to stdout.
+///
+/// If any of the items is an error then iteration stops and a single item
+/// containing the error message is output instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs;
+/// use powerpack::Item;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let items = fs::read_dir(".")?.map(|entry| {
+///     let path = entry?.path();
+///     Ok::<_, std::io::Error>(Item::new(path.display().to_string()))
+/// });
+/// powerpack::try_output(items)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn try_output<I, E>(items: I) -> serde_json::Result<()>
+where
+    I: IntoIterator<Item = Result<Item, E>>,
+    E: fmt::Display,
+{
+    with_stdout(|w| try_output_to(w, items))
+}
+
+/// Shortcut function to output a list of fallible items to the given writer.
+///
+/// See [`try_output`] for more.
+pub fn try_output_to<W, I, E>(w: W, items: I) -> serde_json::Result<()>
+where
+    W: io::Write,
+    I: IntoIterator<Item = Result<Item, E>>,
+    E: fmt::Display,
+{
+    match items.into_iter().collect::<Result<Vec<_>, _>>() {
+        Ok(items) => output_to(w, items),
+        Err(err) => output_to(w, [error_item(&err)]),
+    }
+}
+
+/// Output the result of a workflow.
+///
+/// If the result is `Ok` the items are output to stdout. If the result is an
+/// error then a single item containing the error message is output instead,
+/// the error is written to stderr, and the process exits with a non-zero
+/// status. This makes for a simple `main` function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use powerpack::Item;
+///
+/// fn do_work() -> Result<Vec<Item>, String> {
+///     Ok(vec![Item::new("Hello world!")])
+/// }
+///
+/// fn main() {
+///     powerpack::run(do_work())
+/// }
+/// ```
+pub fn run<I, E>(result: Result<I, E>)
+where
+    I: IntoIterator<Item = Item>,
+    E: fmt::Display,
+{
+    match result {
+        Ok(items) => {
+            if let Err(err) = output(items) {
+                eprintln!("Error: {err}");
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("Error: {err}");
+            let _ = output([error_item(&err)]);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Define the `main` function for a script filter workflow.
+///
+/// The macro takes a closure which receives the user's query, as returned by
+/// [`env::query`], and returns the items to display. If the closure returns an
+/// error then a single item containing the error message is displayed instead
+/// and the process exits with a non-zero status.
+///
+/// For more control use [`run()`], [`Output`], or [`output()`] directly.
+///
+/// # Examples
+///
+/// ```
+/// powerpack::workflow!(|query| {
+///     let item = powerpack::Item::new("Hello world!")
+///         .subtitle(format!("Your query was '{query:?}'"));
+///     Ok(vec![item])
+/// });
+/// ```
+#[cfg(feature = "env")]
+#[macro_export]
+macro_rules! workflow {
+    ($f:expr) => {
+        fn main() {
+            $crate::__workflow_main($f)
+        }
+    };
+}
+
+#[cfg(feature = "env")]
+#[doc(hidden)]
+pub fn __workflow_main<F>(f: F)
+where
+    F: FnOnce(Option<String>) -> Result<Vec<Item>, Box<dyn std::error::Error>>,
+{
+    run(f(env::query()))
+}
+
+/// The query passed to the workflow deserialized from JSON.
+///
+/// This reads back a value set using [`Item::arg_json`] in a connected script
+/// filter. Returns `None` if there is no query, see [`env::query`], or an
+/// error if the query is not valid JSON for `T`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #[derive(serde::Deserialize)]
+/// struct Payload {
+///     id: u64,
+/// }
+///
+/// if let Some(payload) = powerpack::arg_json::<Payload>() {
+///     let payload = payload.expect("valid payload");
+///     println!("{}", payload.id);
+/// }
+/// ```
+#[cfg(feature = "env")]
+pub fn arg_json<T: serde::de::DeserializeOwned>() -> Option<serde_json::Result<T>> {
+    env::query().map(|q| serde_json::from_str(&q))
+}
+
+/// Build an item that displays an error.
+///
+/// The item's title is the error message prefixed with `Error: `, it uses the
+/// system alert icon, and it is not valid so that it cannot be actioned. This
+/// is the item output by [`run()`] and [`try_output()`].
+///
+/// # Examples
+///
+/// ```
+/// let item = powerpack::error_item("something went wrong");
+/// ```
+pub fn error_item(err: impl fmt::Display) -> Item {
+    Item::new(format!("Error: {err}"))
+        .icon(Icon::with_image(
+            "/System/Library/CoreServices/CoreTypes.bundle/Contents/Resources/AlertStopIcon.icns",
+        ))
+        .invalid()
 }
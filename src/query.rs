@@ -0,0 +1,39 @@
+//! Helpers for parsing the script filter query.
+//!
+//! # Examples
+//!
+//! Many workflows use a single keyword followed by a subcommand and its
+//! arguments, e.g. `gh issues powerpack`.
+//!
+//! ```
+//! use powerpack::query;
+//!
+//! let (subcommand, rest) = query::split_command("issues  powerpack ");
+//! assert_eq!(subcommand, Some("issues"));
+//! assert_eq!(rest, "powerpack ");
+//! assert_eq!(query::tokens(rest), ["powerpack"]);
+//! ```
+
+/// Split the query into whitespace separated tokens.
+///
+/// Leading, trailing, and repeated whitespace is ignored.
+pub fn tokens(query: &str) -> Vec<&str> {
+    query.split_whitespace().collect()
+}
+
+/// Split the query into a subcommand and the rest of the query.
+///
+/// The subcommand is the first whitespace separated token, or `None` if the
+/// query is empty or only whitespace. The rest of the query has its leading
+/// whitespace removed but is otherwise unchanged, so that trailing whitespace
+/// can still be used to tell whether the user has finished typing.
+pub fn split_command(query: &str) -> (Option<&str>, &str) {
+    let query = query.trim_start();
+    if query.is_empty() {
+        return (None, "");
+    }
+    match query.split_once(char::is_whitespace) {
+        Some((cmd, rest)) => (Some(cmd), rest.trim_start()),
+        None => (Some(query), ""),
+    }
+}
@@ -0,0 +1,152 @@
+//! Helpers for testing a workflow binary end to end.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use powerpack::testing::run_workflow;
+//! use powerpack::{Item, Output};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let output = run_workflow(
+//!     "target/debug/hello-alfred",
+//!     Some("query"),
+//!     [("alfred_workflow_bundleid", "com.example.hello")],
+//! )?;
+//!
+//! let mut expected = Output::new();
+//! expected.items([Item::new("Hello world!")]);
+//! assert_eq!(output, expected);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Expected output can also be written by hand using [`parse_lenient`], which
+//! tolerates comments and trailing commas.
+
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::Output;
+
+/// Run a workflow binary and parse its script filter output.
+///
+/// The binary is executed with the given query as the first argument and the
+/// given environment variables set, in addition to the current process's
+/// environment. This is how Alfred invokes a script filter, so `env` would
+/// typically contain Alfred variables like `alfred_workflow_bundleid` or
+/// `alfred_workflow_cache`.
+///
+/// # Errors
+///
+/// Returns an error if the binary could not be executed, exits unsuccessfully,
+/// or outputs invalid script filter JSON.
+pub fn run_workflow<I, K, V>(
+    bin: impl AsRef<Path>,
+    query: Option<&str>,
+    env: I,
+) -> io::Result<Output>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    let mut cmd = Command::new(bin.as_ref());
+    if let Some(query) = query {
+        cmd.arg(query);
+    }
+    let output = cmd
+        .envs(env)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`{}` did not exit successfully: {}",
+            bin.as_ref().display(),
+            output.status
+        )));
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Parse script filter JSON that may contain comments and trailing commas.
+///
+/// This is useful for hand-written test fixtures. Both `//` line comments and
+/// `/* */` block comments are supported. Everything else must be valid JSON.
+///
+/// # Examples
+///
+/// ```
+/// use powerpack::testing::parse_lenient;
+/// use powerpack::{Item, Output};
+///
+/// let output = parse_lenient(
+///     r#"{
+///         // the only item
+///         "items": [
+///             {"title": "Hello world!",},
+///         ],
+///     }"#,
+/// )
+/// .unwrap();
+///
+/// let mut expected = Output::new();
+/// expected.items([Item::new("Hello world!")]);
+/// assert_eq!(output, expected);
+/// ```
+pub fn parse_lenient(s: &str) -> serde_json::Result<Output> {
+    serde_json::from_str(&strip_lenient(s))
+}
+
+/// Remove comments and trailing commas, leaving strings untouched.
+fn strip_lenient(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push(c);
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = None;
+                for c in chars.by_ref() {
+                    if prev == Some('*') && c == '/' {
+                        break;
+                    }
+                    prev = Some(c);
+                }
+                out.push(' ');
+            }
+            ']' | '}' => {
+                let trimmed = out.trim_end();
+                if trimmed.ends_with(',') {
+                    out.truncate(trimmed.len() - 1);
+                }
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
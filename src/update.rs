@@ -0,0 +1,279 @@
+//! Check for newer releases of a workflow on GitHub.
+//!
+//! Releases are fetched from the GitHub API using `curl`, which is always
+//! available on macOS. The version of each release is taken from its tag, with
+//! any leading `v` removed, and compared to the workflow version.
+//!
+//! # Examples
+//!
+//! Show an item that installs the update when actioned.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use powerpack::{update, Item};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let day = Duration::from_secs(24 * 60 * 60);
+//! let mut items = vec![Item::new("Hello world!")];
+//! if let Some(release) = update::check_workflow("rossmacarthur/crates.alfredworkflow", day)? {
+//!     items.insert(0, update::install_item(&release));
+//! }
+//! powerpack::output(items)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The item's argument is passed to the action connected to the script
+//! filter, which should call [`install`] when the `powerpack_update` variable
+//! is set. For example, a "Run Script" action that runs the workflow binary
+//! again.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use powerpack::env;
+//!
+//! if env::var("powerpack_update").is_some() {
+//!     if let Some(url) = env::query() {
+//!         powerpack::update::install(&url)?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+pub use semver::Version;
+use serde::Deserialize;
+
+use crate::{env, Item};
+
+/// A release of a workflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// The version of the release.
+    pub version: Version,
+    /// The release tag, e.g. `v1.2.3`.
+    pub tag: String,
+    /// The URL of the release page.
+    pub url: String,
+    /// The URL of the first `.alfredworkflow` file attached to the release.
+    pub download_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawRelease {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    assets: Vec<RawAsset>,
+}
+
+#[derive(Deserialize)]
+struct RawAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Fetch the latest release of a GitHub repository and return it if it is
+/// newer than `current`.
+///
+/// `repo` is the repository in the form `owner/name`. This always makes a
+/// network request, use [`check_cached`] to avoid doing so on every run of
+/// the workflow.
+///
+/// # Errors
+///
+/// Returns an error if the release could not be fetched or parsed.
+pub fn check(repo: &str, current: &Version) -> io::Result<Option<Release>> {
+    let json = fetch_latest(repo)?;
+    newer(&json, current)
+}
+
+/// Same as [`check`] but the latest release is cached in the workflow cache
+/// directory and only fetched again once it is older than `max_age`.
+///
+/// A failed fetch is recorded in the cache too, so that an offline user does
+/// not wait on the network every time the script filter runs. Until the next
+/// attempt, which is made after `max_age` or an hour, whichever is shorter,
+/// this returns `Ok(None)`.
+///
+/// # Errors
+///
+/// Returns an error if the workflow cache directory is not set, or if the
+/// release could not be fetched or parsed.
+pub fn check_cached(
+    repo: &str,
+    current: &Version,
+    max_age: Duration,
+) -> io::Result<Option<Release>> {
+    let path = env::workflow_cache_dir()?.join(format!("update-{}.json", repo.replace('/', "-")));
+    let json = match read_cache(&path) {
+        // An empty file records a failed fetch
+        Some((json, age)) if json.is_empty() && age <= max_age.min(RETRY_AFTER) => {
+            return Ok(None);
+        }
+        Some((json, age)) if !json.is_empty() && age <= max_age => json,
+        _ => fetch_and_cache(repo, &path)?,
+    };
+    newer(&json, current)
+}
+
+/// How long to wait before fetching again after a failed fetch.
+const RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Fetch the latest release and write it to the cache file, or write an empty
+/// file if the fetch failed.
+fn fetch_and_cache(repo: &str, path: &Path) -> io::Result<Vec<u8>> {
+    let result = fetch_latest(repo);
+    let json = result.as_deref().unwrap_or_default();
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp = path.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path)?;
+    result
+}
+
+/// Same as [`check_cached`] but compares against the current workflow
+/// version, see [`env::workflow_version`].
+///
+/// # Errors
+///
+/// Returns an error if the workflow version is not set or is not a valid
+/// semantic version, or if [`check_cached`] fails.
+pub fn check_workflow(repo: &str, max_age: Duration) -> io::Result<Option<Release>> {
+    let version = env::workflow_version().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "`alfred_workflow_version` is not set",
+        )
+    })?;
+    let current = parse_version(&version)?;
+    check_cached(repo, &current, max_age)
+}
+
+/// Build an item that installs the given release when actioned.
+///
+/// The item's argument is the URL of the release's `.alfredworkflow` file, or
+/// the release page if there is none, and it sets the `powerpack_update`
+/// variable so that the connected action can tell it apart from other items.
+/// The action should pass the argument to [`install`].
+pub fn install_item(release: &Release) -> Item {
+    let (subtitle, url) = match &release.download_url {
+        Some(url) => (
+            format!("Press ↵ to install version {}", release.version),
+            url,
+        ),
+        None => (
+            format!(
+                "Press ↵ to open the release page for version {}",
+                release.version
+            ),
+            &release.url,
+        ),
+    };
+    Item::new("A new version of this workflow is available")
+        .subtitle(subtitle)
+        .arg(url)
+        .variable("powerpack_update", "1")
+}
+
+/// Download and open an `.alfredworkflow` file, which makes Alfred install it.
+///
+/// The file is downloaded to the workflow cache directory using `curl` and
+/// opened using `open`. If the URL is not an `.alfredworkflow` file, e.g. a
+/// release page, it is opened in the default browser instead.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be downloaded or opened.
+pub fn install(url: &str) -> io::Result<()> {
+    let target = match url.rsplit('/').next() {
+        Some(name) if name.ends_with(".alfredworkflow") => {
+            let path = env::workflow_cache_dir()?.join(name);
+            fs::create_dir_all(path.parent().unwrap())?;
+            let status = process::Command::new("curl")
+                .args(["--silent", "--show-error", "--fail", "--location"])
+                .arg("--output")
+                .arg(&path)
+                .arg(url)
+                .stdin(process::Stdio::null())
+                .status()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("failed to download `{url}`")));
+            }
+            path.into_os_string()
+        }
+        _ => url.into(),
+    };
+    let status = process::Command::new("open").arg(&target).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "failed to open `{}`",
+            target.to_string_lossy()
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the contents of the file and the time since it was modified.
+fn read_cache(path: &Path) -> Option<(Vec<u8>, Duration)> {
+    let age = path.metadata().ok()?.modified().ok()?.elapsed().ok()?;
+    Some((fs::read(path).ok()?, age))
+}
+
+fn fetch_latest(repo: &str) -> io::Result<Vec<u8>> {
+    let url = format!("https://api.github.com/repos/{repo}/releases/latest");
+    let output = process::Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", "10"])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .args(["--user-agent", "powerpack"])
+        .arg(&url)
+        .stdin(process::Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "failed to fetch `{url}`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn newer(json: &[u8], current: &Version) -> io::Result<Option<Release>> {
+    let RawRelease {
+        tag_name,
+        html_url,
+        assets,
+    } = serde_json::from_slice(json)?;
+    let version = parse_version(&tag_name)?;
+    if version <= *current {
+        return Ok(None);
+    }
+    let download_url = assets
+        .into_iter()
+        .find(|asset| asset.name.ends_with(".alfredworkflow"))
+        .map(|asset| asset.browser_download_url);
+    Ok(Some(Release {
+        version,
+        tag: tag_name,
+        url: html_url,
+        download_url,
+    }))
+}
+
+fn parse_version(s: &str) -> io::Result<Version> {
+    let v = s.trim();
+    let v = v.strip_prefix('v').unwrap_or(v);
+    Version::parse(v).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid version `{s}`: {err}"),
+        )
+    })
+}
@@ -1,6 +1,10 @@
 use std::time::Duration;
 
-use powerpack::{value, Icon, Item, Key, Kind, Modifier, Output};
+use powerpack::{value, Icon, Item, Items, Key, Kind, Loading, Modifier, Output};
+
+/// Serializes tests that set the `alfred_workflow_cache` variable.
+#[cfg(feature = "env")]
+static WORKFLOW_CACHE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
 #[test]
 fn smoke() {
@@ -17,9 +21,8 @@ fn smoke() {
     goldie::assert_json!(output);
 }
 
-#[test]
-fn all() {
-    let item = Item::new("Hello world!")
+fn all_item() -> Item {
+    Item::new("Hello world!")
         .subtitle("This is a subtitle")
         .uid("unique identifier")
         .arg("/path/to/file.jpg")
@@ -31,21 +34,786 @@ fn all() {
         .copy_text("this text will be copied with ⌘C")
         .large_type_text("this text will be displayed with ⌘L")
         .modifier(Modifier::new(Key::Command).subtitle("⌘ changes the subtitle"))
-        .modifier(Modifier::new(Key::Option).arg("/path/to/modified.jpg"))
+        .modifier(
+            Modifier::new(Key::Option)
+                .arg("/path/to/modified.jpg")
+                .copy_text("⌥ changes the copied text"),
+        )
         .modifier(
             Modifier::new_multi([Key::Control, Key::Shift])
                 .icon(Icon::with_image("/path/to/file.png")),
         )
         .modifier(Modifier::new(Key::Shift).valid(false))
+        .modifier(Modifier::new(Key::Function).kind(Kind::File))
         .quicklook_url("https://example.com")
         .action(value!({
             "text": ["one", "two", "three"],
             "url": "https://www.alfredapp.com",
             "file": "~/Desktop",
             "auto": "~/Pictures"
-        }));
+        }))
+}
+
+fn all_output() -> Output {
+    let mut output = Output::new();
+    output
+        .rerun(Duration::from_millis(500))
+        .cache(Duration::from_secs(60), true)
+        .items([all_item()]);
+    output
+}
+
+#[test]
+fn all() {
+    goldie::assert_json!(all_output());
+}
+
+#[test]
+fn all_roundtrip() {
+    let output = all_output();
+    let json = serde_json::to_string(&output).unwrap();
+    assert_eq!(serde_json::from_str::<Output>(&json).unwrap(), output);
+}
+
+#[test]
+fn variables() {
+    let item = Item::new("Loading...")
+        .arg("result")
+        .variable("selected", "result")
+        .variable("source", "item");
 
     let mut output = Output::new();
-    output.rerun(Duration::from_millis(500)).items([item]);
+    output
+        .rerun(Duration::from_millis(100))
+        .variable("attempt", "1")
+        .variable("source", "session")
+        .items([item]);
     goldie::assert_json!(output);
 }
+
+#[test]
+fn item_invalid() {
+    assert_eq!(Item::new("a").invalid(), Item::new("a").valid(false));
+    let json = serde_json::to_value(Item::new("a").invalid()).unwrap();
+    assert_eq!(json["valid"], false);
+}
+
+#[test]
+fn item_getters() {
+    let item = all_item();
+    assert_eq!(item.title(), "Hello world!");
+    assert_eq!(item.get_subtitle(), Some("This is a subtitle"));
+    assert_eq!(item.get_uid(), Some("unique identifier"));
+    assert_eq!(item.get_arg(), Some("/path/to/file.jpg"));
+    assert_eq!(item.get_args(), ["/path/to/file.jpg"]);
+    assert_eq!(item.get_icon(), Some(&Icon::with_type("public.jpeg")));
+    assert_eq!(item.get_valid(), Some(true));
+    assert_eq!(item.get_matches(), Some("use this to filter"));
+    assert_eq!(item.get_autocomplete(), Some("to this"));
+    assert_eq!(item.get_kind(), Kind::FileSkipCheck);
+    assert_eq!(
+        item.get_copy_text(),
+        Some("this text will be copied with ⌘C")
+    );
+    assert_eq!(
+        item.get_large_type_text(),
+        Some("this text will be displayed with ⌘L")
+    );
+    assert_eq!(item.get_quicklook_url(), Some("https://example.com"));
+
+    let item = Item::new("a").variable("k", "v");
+    assert_eq!(item.get_subtitle(), None);
+    assert_eq!(item.get_arg(), None);
+    assert!(item.get_args().is_empty());
+    assert_eq!(item.get_variable("k"), Some("v"));
+}
+
+#[test]
+fn item_setters() {
+    let mut item = Item::new("a").match_title_and_subtitle();
+    item.set_title("b")
+        .set_subtitle("c")
+        .set_uid("d")
+        .set_arg("e")
+        .set_icon("f.png")
+        .set_valid(false)
+        .set_autocomplete("g")
+        .set_kind(Kind::File)
+        .set_modifier(Modifier::new(Key::Command).arg("h"))
+        .set_copy_text("i")
+        .set_large_type_text("j")
+        .set_quicklook_url("k")
+        .set_variable("l", "m");
+    assert_eq!(
+        item,
+        Item::new("b")
+            .subtitle("c")
+            .match_title_and_subtitle()
+            .uid("d")
+            .arg("e")
+            .icon("f.png")
+            .valid(false)
+            .autocomplete("g")
+            .kind(Kind::File)
+            .modifier(Modifier::new(Key::Command).arg("h"))
+            .copy_text("i")
+            .large_type_text("j")
+            .quicklook_url("k")
+            .variable("l", "m")
+    );
+    assert_eq!(item.get_matches(), Some("b c"));
+    item.set_matches("n");
+    assert_eq!(item.get_matches(), Some("n"));
+}
+
+#[test]
+fn item_quicklook_file() {
+    let get = |path| {
+        Item::new("a")
+            .quicklook_file(path)
+            .get_quicklook_url()
+            .map(str::to_owned)
+    };
+    assert_eq!(get("/tmp/a.txt").as_deref(), Some("/tmp/a.txt"));
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(get("a.txt"), Some(cwd.join("a.txt").display().to_string()));
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = std::path::PathBuf::from(home);
+        assert_eq!(
+            get("~/a.txt"),
+            Some(home.join("a.txt").display().to_string())
+        );
+    }
+}
+
+#[test]
+fn item_arg_json() {
+    let item = Item::new("a")
+        .arg_json(&value!({"id": 1, "tags": ["x"]}))
+        .unwrap();
+    assert_eq!(item.get_arg(), Some(r#"{"id":1,"tags":["x"]}"#));
+
+    let map = std::collections::HashMap::from([((1, 2), 3)]);
+    assert!(Item::new("a").arg_json(&map).is_err());
+}
+
+#[test]
+fn item_uid_from_arg() {
+    assert_eq!(
+        Item::new("a").arg("x").uid_from_arg(),
+        Item::new("a").arg("x").uid("x")
+    );
+    assert_eq!(
+        Item::new("a")
+            .args::<[&str; 0], &str>(["x", "y"])
+            .uid_from_arg(),
+        Item::new("a")
+            .args::<[&str; 0], &str>(["x", "y"])
+            .uid("x\ty")
+    );
+    assert_eq!(Item::new("a").uid_from_arg(), Item::new("a"));
+}
+
+#[test]
+fn item_sanitize() {
+    let item = Item::new("\ta\n b ")
+        .subtitle("multi\r\nline\n\nsubtitle")
+        .modifier(Modifier::new(Key::Command).subtitle(" x\ty "))
+        .sanitize();
+    assert_eq!(
+        item,
+        Item::new("a b")
+            .subtitle("multi line subtitle")
+            .modifier(Modifier::new(Key::Command).subtitle("x y"))
+    );
+    assert_eq!(Item::new("  a  b ").sanitize(), Item::new("a  b"));
+}
+
+#[test]
+fn modifier_empty_subtitle() {
+    let item = Item::new("a")
+        .subtitle("b")
+        .modifier(Modifier::new(Key::Command).arg("x"))
+        .modifier(Modifier::new(Key::Option).subtitle(""));
+    let json = serde_json::to_value(item).unwrap();
+    assert!(json["mods"]["cmd"].get("subtitle").is_none());
+    assert_eq!(json["mods"]["alt"]["subtitle"], "");
+}
+
+#[test]
+fn modifiers_order() {
+    let cmd = Modifier::new(Key::Command).subtitle("cmd");
+    let alt = Modifier::new(Key::Option).subtitle("alt");
+    let a = Item::new("a").modifier(cmd.clone()).modifier(alt.clone());
+    let b = Item::new("a").modifier(alt).modifier(cmd);
+    let json = serde_json::to_string(&a).unwrap();
+    assert_eq!(json, serde_json::to_string(&b).unwrap());
+    assert!(json.find("\"cmd\"").unwrap() < json.find("\"alt\"").unwrap());
+}
+
+#[test]
+fn fold_diacritics() {
+    assert_eq!(powerpack::fold_diacritics("café"), "cafe");
+    assert_eq!(powerpack::fold_diacritics("cafe\u{301}"), "cafe");
+    assert_eq!(powerpack::fold_diacritics("Straße Łódź"), "Strasse Lodz");
+    assert_eq!(powerpack::fold_diacritics("日本"), "日本");
+    assert_eq!(
+        Item::new("a").matches_folded("café"),
+        Item::new("a").matches("café cafe")
+    );
+    assert_eq!(
+        Item::new("a").matches_folded("cafe"),
+        Item::new("a").matches("cafe")
+    );
+}
+
+#[test]
+fn query_split_command() {
+    use powerpack::query::{split_command, tokens};
+
+    assert_eq!(split_command(""), (None, ""));
+    assert_eq!(split_command("  "), (None, ""));
+    assert_eq!(split_command("issues"), (Some("issues"), ""));
+    assert_eq!(split_command(" issues "), (Some("issues"), ""));
+    assert_eq!(
+        split_command("issues  rust lang "),
+        (Some("issues"), "rust lang ")
+    );
+    assert_eq!(tokens("  rust \t lang "), ["rust", "lang"]);
+    assert!(tokens(" ").is_empty());
+}
+
+#[test]
+fn item_match_title_and_subtitle() {
+    assert_eq!(
+        Item::new("a").subtitle("b").match_title_and_subtitle(),
+        Item::new("a")
+            .match_title_and_subtitle()
+            .match_title_and_subtitle()
+            .subtitle("b"),
+    );
+    let json =
+        serde_json::to_value(Item::new("a").match_title_and_subtitle().subtitle("b")).unwrap();
+    assert_eq!(json["match"], "a b");
+    let json =
+        serde_json::to_value(Item::new("a").matches("x").match_title_and_subtitle()).unwrap();
+    assert_eq!(json["match"], "x");
+    let json =
+        serde_json::to_value(Item::new("a").match_title_and_subtitle().matches("x")).unwrap();
+    assert_eq!(json["match"], "x");
+}
+
+#[test]
+fn key_from_str() {
+    for (s, key) in [
+        ("cmd", Key::Command),
+        ("Command", Key::Command),
+        ("alt", Key::Option),
+        ("option", Key::Option),
+        ("ctrl", Key::Control),
+        ("control", Key::Control),
+        ("shift", Key::Shift),
+        ("fn", Key::Function),
+    ] {
+        assert_eq!(s.parse::<Key>().unwrap(), key);
+        assert_eq!(Key::try_from(s).unwrap(), key);
+    }
+    assert_eq!(
+        "super".parse::<Key>().unwrap_err().to_string(),
+        "unknown modifier key `super`"
+    );
+}
+
+#[test]
+fn kind_display_from_str() {
+    for kind in [Kind::Default, Kind::File, Kind::FileSkipCheck] {
+        let s = kind.to_string();
+        assert_eq!(s.parse::<Kind>().unwrap(), kind);
+        assert_eq!(serde_json::to_value(kind).unwrap(), s.as_str());
+    }
+    assert!("folder".parse::<Kind>().is_err());
+}
+
+#[test]
+fn item_arg_os() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    assert_eq!(
+        Item::new("a").arg_os("/path/to/file"),
+        Item::new("a").arg("/path/to/file")
+    );
+    assert_eq!(
+        Item::new("a").arg_os(OsStr::from_bytes(b"/path/to/\xff")),
+        Item::new("a").arg("/path/to/\u{FFFD}")
+    );
+}
+
+#[test]
+fn items() {
+    let items: Items = [("a", 1), ("b", 3), ("a", 2), ("c", 2)]
+        .into_iter()
+        .map(|(uid, score)| Item::new(score.to_string()).uid(uid))
+        .collect();
+
+    let items = items
+        .dedup_by_uid()
+        .sorted_by_score(|item| item.title().to_owned())
+        .truncate(2);
+
+    let mut output = Output::new();
+    output.items(items);
+    assert_eq!(
+        output,
+        *Output::new().items([Item::new("3").uid("b"), Item::new("2").uid("c")])
+    );
+}
+
+/// Validates a value against the subset of JSON Schema used by
+/// `json_schema()`, recording each property that is visited as
+/// `<definition>.<property>`. Objects with `properties` are treated as closed,
+/// so a serialized field missing from the schema is an error.
+#[cfg(feature = "schema")]
+fn validate_schema(
+    root: &serde_json::Value,
+    schema: &serde_json::Value,
+    name: &str,
+    value: &serde_json::Value,
+    seen: &mut std::collections::BTreeSet<String>,
+) -> Result<(), String> {
+    if let Some(r) = schema["$ref"].as_str() {
+        let def = r.strip_prefix("#/definitions/").unwrap();
+        return validate_schema(root, &root["definitions"][def], def, value, seen);
+    }
+    if let Some(schemas) = schema["oneOf"].as_array() {
+        let matches = schemas
+            .iter()
+            .filter(|s| validate_schema(root, s, name, value, seen).is_ok())
+            .count();
+        if matches != 1 {
+            return Err(format!("`{name}` matched {matches} schemas: {value}"));
+        }
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        if !values.contains(value) {
+            return Err(format!("`{name}` has unknown value {value}"));
+        }
+    }
+    let ok = match schema["type"].as_str() {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("number") => value.is_number(),
+        Some("integer") => value.is_u64() || value.is_i64(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+    if !ok {
+        return Err(format!("`{name}` has the wrong type: {value}"));
+    }
+    if let Some(n) = value.as_f64() {
+        let min = schema["minimum"].as_f64().unwrap_or(f64::MIN);
+        let max = schema["maximum"].as_f64().unwrap_or(f64::MAX);
+        if n < min || n > max {
+            return Err(format!("`{name}` is out of range: {value}"));
+        }
+    }
+    if let Some(items) = value.as_array() {
+        for item in items {
+            validate_schema(root, &schema["items"], name, item, seen)?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        for key in schema["required"].as_array().into_iter().flatten() {
+            if !object.contains_key(key.as_str().unwrap()) {
+                return Err(format!("`{name}` is missing required {key}"));
+            }
+        }
+        for (key, v) in object {
+            if schema["propertyNames"].is_object() {
+                let key = serde_json::Value::from(key.as_str());
+                validate_schema(root, &schema["propertyNames"], name, &key, seen)?;
+            }
+            if let Some(prop) = schema["properties"].get(key) {
+                let name = format!("{name}.{key}");
+                seen.insert(name.clone());
+                validate_schema(root, prop, &name, v, seen)?;
+            } else if schema["additionalProperties"].is_object() {
+                validate_schema(root, &schema["additionalProperties"], name, v, seen)?;
+            } else if schema["properties"].is_object() {
+                return Err(format!("`{name}` has unknown property `{key}`"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns all properties described by the schema, named like in
+/// `validate_schema`.
+#[cfg(feature = "schema")]
+fn schema_properties(
+    schema: &serde_json::Value,
+    name: &str,
+    out: &mut std::collections::BTreeSet<String>,
+) {
+    for (key, prop) in schema["properties"].as_object().into_iter().flatten() {
+        let name = format!("{name}.{key}");
+        schema_properties(prop, &name, out);
+        out.insert(name);
+    }
+}
+
+#[cfg(feature = "schema")]
+#[test]
+fn json_schema() {
+    let schema: serde_json::Value = serde_json::from_str(&powerpack::json_schema()).unwrap();
+
+    let mut other = Output::new();
+    other.skip_knowledge(true).variable("a", "1").items([
+        Item::new("a")
+            .args::<[&str; 0], &str>(["b", "c"])
+            .icon(Icon::with_file_icon("/path/to/file"))
+            .kind(Kind::File)
+            .variable("d", "2"),
+        Item::new("e")
+            .icon(Icon::with_image("icon.png"))
+            .modifier(Modifier::new(Key::Command).large_type_text("f")),
+    ]);
+
+    let mut seen = std::collections::BTreeSet::new();
+    for output in [all_output(), other] {
+        let value = serde_json::to_value(output).unwrap();
+        validate_schema(&schema, &schema, "Output", &value, &mut seen).unwrap();
+    }
+
+    // Every property in the schema is serialized by one of the outputs above,
+    // so the schema can't describe fields that don't exist
+    let mut expected = std::collections::BTreeSet::new();
+    schema_properties(&schema, "Output", &mut expected);
+    for (name, def) in schema["definitions"].as_object().unwrap() {
+        schema_properties(def, name, &mut expected);
+    }
+    let missing: Vec<_> = expected.difference(&seen).collect();
+    assert!(missing.is_empty(), "properties not covered: {missing:?}");
+}
+
+#[test]
+fn output_rerun_secs() {
+    let mut expected = Output::new();
+    expected.rerun(Duration::from_millis(500));
+    assert_eq!(*Output::new().rerun_secs(0.5), expected);
+    assert_eq!(*Output::new().rerun_secs(-1.0), Output::new());
+
+    for (secs, expected) in [(0.3, "0.3"), (0.7, "0.7"), (1.25, "1.25")] {
+        let json = serde_json::to_value(Output::new().rerun_secs(secs)).unwrap();
+        assert_eq!(json["rerun"].to_string(), expected);
+    }
+}
+
+#[test]
+fn output_merge() {
+    let mut output = Output::new();
+    output
+        .rerun(Duration::from_secs(2))
+        .cache(Duration::from_secs(60), false)
+        .variable("a", "1")
+        .variable("b", "1")
+        .items([Item::new("a")]);
+
+    let mut other = Output::new();
+    other
+        .rerun(Duration::from_secs(1))
+        .cache(Duration::from_secs(120), true)
+        .skip_knowledge(true)
+        .variable("b", "2")
+        .variable("c", "2")
+        .items([Item::new("b")]);
+
+    let mut expected = Output::new();
+    expected
+        .rerun(Duration::from_secs(1))
+        .cache(Duration::from_secs(60), false)
+        .skip_knowledge(true)
+        .variable("a", "1")
+        .variable("b", "2")
+        .variable("c", "2")
+        .items([Item::new("a"), Item::new("b")]);
+
+    assert_eq!(*output.merge(other), expected);
+    assert_eq!(*Output::new().merge(expected.clone()), expected);
+}
+
+#[test]
+fn output_write_ndjson() {
+    let mut buf = Vec::new();
+    Output::new()
+        .variable("a", "1")
+        .items([Item::new("a"), Item::new("b").arg("c")])
+        .write_ndjson(&mut buf)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "{\"title\":\"a\"}\n{\"title\":\"b\",\"arg\":\"c\"}\n"
+    );
+
+    let mut buf = Vec::new();
+    Output::new()
+        .placeholder_when_empty(Item::new("empty"))
+        .write_ndjson(&mut buf)
+        .unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), "{\"title\":\"empty\"}\n");
+}
+
+#[test]
+fn placeholder_when_empty() {
+    let write = |output: &Output| {
+        let mut buf = Vec::new();
+        output.write(&mut buf).unwrap();
+        serde_json::from_slice::<serde_json::Value>(&buf).unwrap()
+    };
+    let placeholder = Item::new("No matches found").invalid();
+
+    let mut output = Output::new();
+    output.placeholder_when_empty(placeholder);
+    assert_eq!(write(&output)["items"][0]["title"], "No matches found");
+
+    output.items([Item::new("a")]);
+    assert_eq!(write(&output)["items"], value!([{"title": "a"}]));
+}
+
+#[test]
+fn try_output() {
+    let write = |items: Vec<Result<Item, &str>>| {
+        let mut buf = Vec::new();
+        powerpack::try_output_to(&mut buf, items).unwrap();
+        serde_json::from_slice::<serde_json::Value>(&buf).unwrap()
+    };
+
+    let json = write(vec![Ok(Item::new("a")), Ok(Item::new("b"))]);
+    assert_eq!(json["items"], value!([{"title": "a"}, {"title": "b"}]));
+
+    let json = write(vec![Ok(Item::new("a")), Err("oops"), Ok(Item::new("b"))]);
+    assert_eq!(json["items"].as_array().unwrap().len(), 1);
+    assert_eq!(json["items"][0]["title"], "Error: oops");
+    assert_eq!(json["items"][0]["valid"], false);
+}
+
+#[test]
+fn loading() {
+    let loading = Loading::new("powerpack_test_loading").rerun(Duration::from_secs(1));
+    assert!(loading.is_first_run());
+
+    let mut expected = Output::new();
+    expected
+        .rerun(Duration::from_secs(1))
+        .variable("powerpack_test_loading", "1")
+        .items([Item::new("Loading...").invalid()]);
+    assert_eq!(loading.output(None::<Vec<Item>>), expected);
+
+    let mut expected = Output::new();
+    expected.items([Item::new("Done")]);
+    assert_eq!(loading.output(Some([Item::new("Done")])), expected);
+}
+
+#[test]
+fn icon_from_path() {
+    let icon = Icon::with_image("./icon.png");
+    assert_eq!(Icon::from("./icon.png"), icon);
+    assert_eq!(Icon::from(String::from("./icon.png")), icon);
+    assert_eq!(Icon::from(std::path::PathBuf::from("./icon.png")), icon);
+    assert_eq!(Item::new("a").icon("./icon.png"), Item::new("a").icon(icon));
+}
+
+#[test]
+fn icon_try_with_type() {
+    assert_eq!(
+        Icon::try_with_type("public.jpeg").unwrap(),
+        Icon::with_type("public.jpeg")
+    );
+    assert_eq!(
+        Icon::try_with_type("public.jpg").unwrap_err().to_string(),
+        "unrecognized UTI `public.jpg`"
+    );
+    for ext in ["pdf", "jpg", "zip", "rs", "app", "unknown"] {
+        let icon = Icon::with_extension(ext);
+        let json = serde_json::to_value(&icon).unwrap();
+        let uti = json["path"].as_str().unwrap();
+        assert_eq!(Icon::try_with_type(uti).unwrap(), icon);
+    }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn icon_themed() {
+    let themed = |background: Option<&str>| {
+        match background {
+            Some(bg) => std::env::set_var("alfred_theme_background", bg),
+            None => std::env::remove_var("alfred_theme_background"),
+        }
+        Icon::themed("light.png", "dark.png")
+    };
+    let light = Icon::with_image("light.png");
+    let dark = Icon::with_image("dark.png");
+    assert_eq!(themed(Some("rgba(255,255,255,0.98)")), light);
+    assert_eq!(themed(Some("rgba(30, 30, 30, 1.00)")), dark);
+    assert_eq!(themed(Some("not a color")), light);
+    assert_eq!(themed(None), light);
+
+    std::env::set_var("alfred_theme_background", "rgba(30,30,30,0.5)");
+    assert_eq!(powerpack::env::theme_background(), Some((30, 30, 30, 0.5)));
+    std::env::remove_var("alfred_theme_background");
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn env_try_var() {
+    std::env::set_var("POWERPACK_TEST_TRY_VAR", "a");
+    assert_eq!(
+        powerpack::env::try_var("POWERPACK_TEST_TRY_VAR").as_deref(),
+        Some("a")
+    );
+    assert_eq!(powerpack::env::try_var(""), None);
+    assert_eq!(powerpack::env::try_var("A=B"), None);
+    assert_eq!(powerpack::env::try_var("A\0B"), None);
+    std::env::remove_var("POWERPACK_TEST_TRY_VAR");
+}
+
+#[test]
+fn icon_with_extension() {
+    assert_eq!(
+        Icon::with_extension("rs"),
+        Icon::with_type("public.source-code")
+    );
+    assert_eq!(Icon::with_extension(".JPG"), Icon::with_type("public.jpeg"));
+    assert_eq!(Icon::with_extension("nope"), Icon::with_type("public.data"));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn testing_parse_lenient() {
+    let output = powerpack::testing::parse_lenient(
+        r#"{
+            /* rerun every
+               half second */
+            "rerun": 0.5,
+            "items": [
+                // a comment, with a comma
+                {"title": "a // b", "subtitle": "c,]",},
+                {"title": "\"}",},
+            ],
+        }"#,
+    )
+    .unwrap();
+
+    let mut expected = Output::new();
+    expected
+        .rerun(Duration::from_millis(500))
+        .items([Item::new("a // b").subtitle("c,]"), Item::new("\"}")]);
+    assert_eq!(output, expected);
+    assert!(powerpack::testing::parse_lenient("{\"items\": [}").is_err());
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn icon_from_bytes() {
+    let _guard = WORKFLOW_CACHE_LOCK.lock().unwrap();
+    let dir = std::env::temp_dir().join("powerpack-test-icon-from-bytes");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::env::set_var("alfred_workflow_cache", &dir);
+
+    let a = Icon::from_bytes(b"not really a png", "png").unwrap();
+    let b = Icon::from_bytes(b"not really a png", "png").unwrap();
+    let c = Icon::from_bytes(b"another image", "png").unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_eq!(std::fs::read_dir(dir.join("icons")).unwrap().count(), 2);
+
+    for ext in ["", "../png", "a/b", "tar.gz", ".."] {
+        let err = Icon::from_bytes(b"not really a png", ext).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    assert_eq!(std::fs::read_dir(dir.join("icons")).unwrap().count(), 2);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "update")]
+#[test]
+fn update_check_cached() {
+    use powerpack::update::{self, Version};
+
+    let _guard = WORKFLOW_CACHE_LOCK.lock().unwrap();
+
+    let dir = std::env::temp_dir().join("powerpack-test-update-check-cached");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("update-owner-repo.json"),
+        serde_json::to_vec(&value!({
+            "tag_name": "v1.2.0",
+            "html_url": "https://github.com/owner/repo/releases/tag/v1.2.0",
+            "assets": [
+                {"name": "checksums.txt", "browser_download_url": "https://example.com/checksums.txt"},
+                {"name": "repo.alfredworkflow", "browser_download_url": "https://example.com/repo.alfredworkflow"}
+            ]
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+    std::env::set_var("alfred_workflow_cache", &dir);
+
+    let day = Duration::from_secs(24 * 60 * 60);
+    let release = update::check_cached("owner/repo", &Version::new(1, 1, 9), day)
+        .unwrap()
+        .unwrap();
+    assert_eq!(release.version, Version::new(1, 2, 0));
+    assert_eq!(release.tag, "v1.2.0");
+    assert_eq!(
+        release.download_url.as_deref(),
+        Some("https://example.com/repo.alfredworkflow")
+    );
+    let item = serde_json::to_value(update::install_item(&release)).unwrap();
+    assert_eq!(item["arg"], "https://example.com/repo.alfredworkflow");
+    assert_eq!(item["subtitle"], "Press ↵ to install version 1.2.0");
+    assert_eq!(item["variables"]["powerpack_update"], "1");
+    assert!(
+        update::check_cached("owner/repo", &Version::new(1, 2, 0), day)
+            .unwrap()
+            .is_none()
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "update")]
+#[test]
+fn update_check_cached_failed() {
+    use powerpack::update::{self, Version};
+
+    let _guard = WORKFLOW_CACHE_LOCK.lock().unwrap();
+
+    let dir = std::env::temp_dir().join("powerpack-test-update-check-cached-failed");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::env::set_var("alfred_workflow_cache", &dir);
+    // Make the request fail straight away without touching the network
+    std::env::set_var("https_proxy", "http://127.0.0.1:1");
+
+    let day = Duration::from_secs(24 * 60 * 60);
+    let path = dir.join("update-owner-repo.json");
+    assert!(update::check_cached("owner/repo", &Version::new(1, 0, 0), day).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), b"");
+
+    // The failure is cached, so this does not fetch again
+    assert!(
+        update::check_cached("owner/repo", &Version::new(1, 0, 0), day)
+            .unwrap()
+            .is_none()
+    );
+
+    // Once the backoff has passed it fetches again
+    assert!(update::check_cached("owner/repo", &Version::new(1, 0, 0), Duration::ZERO).is_err());
+
+    std::env::remove_var("https_proxy");
+    std::fs::remove_dir_all(dir).unwrap();
+}